@@ -1,4 +1,7 @@
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use proc_macro::TokenStream;
 use quote::quote;
@@ -8,26 +11,35 @@ pub fn include_programs(_input: TokenStream) -> TokenStream {
     let current_dir = env::current_dir().unwrap();
     let program_dir = current_dir.join(PathBuf::from("kernel/programs"));
 
-    let program_files = fs::read_dir(program_dir)
+    let mut program_files = fs::read_dir(&program_dir)
         .expect("No programs directory is present")
         .flatten()
-        .filter_map(|file| {
-            let path = file.path();
-
-            if path.is_file() {
-                let contents = fs::read(path).unwrap();
-                return Some(contents);
-            }
-
-            None
+        .map(|file| file.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let (name, service) = parse_program(&path)?;
+            let contents = fs::read(&path).unwrap();
+            Some((name, service, contents))
         })
         .collect::<Vec<_>>();
 
+    // read_dir returns the files in the order of the file system, sorting
+    // them keeps the task ids the same across builds
+    program_files.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+    if program_files.is_empty() {
+        let message = format!(
+            "no programs found in {}, only files ending in .bin or without an extension are included",
+            program_dir.display()
+        );
+        return quote! { compile_error!(#message) }.into();
+    }
+
     let tokens: Vec<_> = program_files
         .iter()
-        .map(|inner_vec| {
+        .map(|(name, service, inner_vec)| {
             quote! {
-            &[#(#inner_vec), *]
+            (#name, &[#(#inner_vec), *], #service)
             }
         })
         .collect();
@@ -42,3 +54,25 @@ pub fn include_programs(_input: TokenStream) -> TokenStream {
 
     expanded.into()
 }
+
+// Programs are raw binaries, which have no extension or end in .bin. A
+// `.service` in front of that, e.g. logger.service.bin, marks a program which
+// is restarted when it fails. Hidden files like .gitkeep have no extension
+// either, so they are skipped.
+fn parse_program(path: &Path) -> Option<(String, bool)> {
+    let file_name = path.file_name()?.to_str()?;
+    if file_name.starts_with('.') {
+        return None;
+    }
+
+    let (stem, binary) = match file_name.strip_suffix(".bin") {
+        Some(stem) => (stem, true),
+        None => (file_name, false),
+    };
+
+    if let Some(name) = stem.strip_suffix(".service") {
+        return Some((name.to_owned(), true));
+    }
+
+    (binary || !stem.contains('.')).then(|| (stem.to_owned(), false))
+}
@@ -1,10 +1,40 @@
 use shared::{gpio::pins::{GPIO1_21, GPIO1_22, GPIO1_23}, interrupts::disable_interrupts};
 
 use super::gpio;
+use crate::{
+    internals::tasks::{scheduler, ExitReason, Task},
+    peripherals::uart,
+};
 
+// A task accessed memory it has no access to, it is terminated and the
+// kernel continues with the next task
 #[no_mangle]
-fn data_abort_handler() {
+extern "C" fn data_abort_handler(fault_address: u32, status: u32) {
+    let scheduler = scheduler();
+    if let Some(id) = scheduler.current_task_id() {
+        // the stack ran into the unmapped page below it
+        if Task::in_guard_page(fault_address) {
+            uart::println(format_args!("stack overflow in task {}", id));
+            scheduler.terminate(id, ExitReason::StackOverflow);
+        } else {
+            uart::println(format_args!(
+                "data abort in task {} at {:#010x} (status {:#x})",
+                id, fault_address, status
+            ));
+            scheduler.terminate(id, ExitReason::DataAbort);
+        }
+    }
+
+    scheduler.cycle();
+}
+
+#[no_mangle]
+extern "C" fn kernel_data_abort_handler(fault_address: u32, status: u32) -> ! {
     gpio::write(GPIO1_21, true);
+    uart::println(format_args!(
+        "data abort in kernel at {:#010x} (status {:#x})",
+        fault_address, status
+    ));
 
     loop {}
 }
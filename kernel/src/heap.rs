@@ -11,6 +11,16 @@ pub fn initialize() {
     }
 }
 
+pub fn used() -> usize {
+    let allocator = &raw const ALLOCATOR;
+    unsafe { (*allocator).used() }
+}
+
+pub fn remaining() -> usize {
+    let allocator = &raw const ALLOCATOR;
+    unsafe { (*allocator).remaining() }
+}
+
 #[global_allocator]
 static mut ALLOCATOR: BumpAllocator = BumpAllocator::new();
 
@@ -0,0 +1,86 @@
+use shared::{
+    interrupts::CriticalSection,
+    kernel::{BARRIER_INVALID, MAX_TASKS},
+};
+
+use super::tasks::{scheduler, Event};
+
+const MAX_BARRIERS: usize = 8;
+
+// Waiting tasks are blocked on Event::Barrier with its id, they are only
+// tracked here so a terminated task doesn't count as arrived anymore
+struct Barrier {
+    parties: u32,
+    waiting: [bool; MAX_TASKS],
+    // the barrier is destroyed together with the task which created it
+    creator: usize,
+}
+
+impl Barrier {
+    fn arrived(&self) -> u32 {
+        self.waiting.iter().filter(|waiting| **waiting).count() as u32
+    }
+}
+
+static BARRIERS: CriticalSection<[Option<Barrier>; MAX_BARRIERS]> =
+    CriticalSection::new([const { None }; MAX_BARRIERS]);
+
+pub fn create(parties: u32, creator: usize) -> Option<usize> {
+    if parties == 0 {
+        return None;
+    }
+
+    let mut barriers = BARRIERS.lock();
+    let id = barriers.iter().position(|barrier| barrier.is_none())?;
+    barriers[id] = Some(Barrier {
+        parties,
+        waiting: [false; MAX_TASKS],
+        creator,
+    });
+
+    Some(id)
+}
+
+// Registers the arrival of a task, returns true for the last task to arrive,
+// which resets the barrier so it can be used for the next phase
+pub fn arrive(id: usize, task: usize) -> Option<bool> {
+    let mut barriers = BARRIERS.lock();
+    let barrier = barriers.get_mut(id)?.as_mut()?;
+
+    barrier.waiting[task] = true;
+    if barrier.arrived() < barrier.parties {
+        return Some(false);
+    }
+
+    barrier.waiting = [false; MAX_TASKS];
+    Some(true)
+}
+
+// Frees the slot of a barrier the task created, tasks still waiting at it are
+// woken with BARRIER_INVALID
+pub fn destroy(id: usize, task: usize) -> bool {
+    let mut barriers = BARRIERS.lock();
+    let Some(slot) = barriers.get_mut(id) else {
+        return false;
+    };
+
+    if slot.as_ref().is_none_or(|barrier| barrier.creator != task) {
+        return false;
+    }
+
+    *slot = None;
+    scheduler().wake_all(Event::Barrier(id), BARRIER_INVALID);
+    true
+}
+
+// Removes a terminated task from the barriers it waits at and destroys the
+// ones it created, so their slots can be used again
+pub fn release_all(task: usize) {
+    for id in 0..MAX_BARRIERS {
+        if let Some(barrier) = BARRIERS.lock()[id].as_mut() {
+            barrier.waiting[task] = false;
+        }
+
+        destroy(id, task);
+    }
+}
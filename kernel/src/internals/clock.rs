@@ -2,25 +2,37 @@ use shared::sys::write_addr;
 
 enum ClockModule {
     CmPer = 0x44E0_0000,
+    CmWkup = 0x44E0_0400,
 }
 
 pub enum FuncClock {
-    I2C2 = 0x44,
-    I2C1 = 0x48,
-    Timer7 = 0x7C,
-    Timer2 = 0x80,
-    Timer3 = 0x84,
-    Timer4 = 0x88,
-    Gpio1 = 0xAC,
-    Gpio2 = 0xB0,
-    Gpio3 = 0xB4,
-    Timer5 = 0xEC,
-    Timer6 = 0xF0,
+    Gpio0,
+    I2C0,
+    I2C2,
+    I2C1,
+    Timer7,
+    Timer2,
+    Timer3,
+    Timer4,
+    Gpio1,
+    Gpio2,
+    Gpio3,
+    Timer5,
+    Timer6,
+    Uart0,
+    Epwmss0,
+    Epwmss1,
+    Epwmss2,
+    AdcTsc,
+    Spi0,
+    Wdt1,
 }
 
 impl FuncClock {
     fn clock_module(&self) -> ClockModule {
         match self {
+            FuncClock::Gpio0 => ClockModule::CmWkup,
+            FuncClock::I2C0 => ClockModule::CmWkup,
             FuncClock::I2C2 => ClockModule::CmPer,
             FuncClock::I2C1 => ClockModule::CmPer,
             FuncClock::Timer7 => ClockModule::CmPer,
@@ -32,11 +44,44 @@ impl FuncClock {
             FuncClock::Gpio3 => ClockModule::CmPer,
             FuncClock::Timer5 => ClockModule::CmPer,
             FuncClock::Timer6 => ClockModule::CmPer,
+            FuncClock::Uart0 => ClockModule::CmWkup,
+            FuncClock::Epwmss0 => ClockModule::CmPer,
+            FuncClock::Epwmss1 => ClockModule::CmPer,
+            FuncClock::Epwmss2 => ClockModule::CmPer,
+            FuncClock::AdcTsc => ClockModule::CmWkup,
+            FuncClock::Spi0 => ClockModule::CmPer,
+            FuncClock::Wdt1 => ClockModule::CmWkup,
+        }
+    }
+
+    // offset of the CLKCTRL register within its clock module
+    fn offset(&self) -> u32 {
+        match self {
+            FuncClock::Gpio0 => 0x08,
+            FuncClock::I2C0 => 0xB8,
+            FuncClock::I2C2 => 0x44,
+            FuncClock::I2C1 => 0x48,
+            FuncClock::Timer7 => 0x7C,
+            FuncClock::Timer2 => 0x80,
+            FuncClock::Timer3 => 0x84,
+            FuncClock::Timer4 => 0x88,
+            FuncClock::Gpio1 => 0xAC,
+            FuncClock::Gpio2 => 0xB0,
+            FuncClock::Gpio3 => 0xB4,
+            FuncClock::Timer5 => 0xEC,
+            FuncClock::Timer6 => 0xF0,
+            FuncClock::Uart0 => 0xB4,
+            FuncClock::Epwmss0 => 0xD4,
+            FuncClock::Epwmss1 => 0xCC,
+            FuncClock::Epwmss2 => 0xD8,
+            FuncClock::AdcTsc => 0xBC,
+            FuncClock::Spi0 => 0x4C,
+            FuncClock::Wdt1 => 0xD4,
         }
     }
 
     pub fn enable(self) {
-        write_addr(self.clock_module() as u32 + self as u32, 0x2);
+        write_addr(self.clock_module() as u32 + self.offset(), 0x2);
     }
 }
 
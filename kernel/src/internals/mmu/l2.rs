@@ -1,11 +1,13 @@
-use core::arch::asm;
+use core::{arch::asm, fmt};
 
 use super::l1::{L1PointerTableEntry, LEVEL1_PAGE_TABLE};
 
 const BASE_ADDRESS: u32 = 0x4030_0000;
 const PAGE_SIZE_BITS: u32 = 12;
 const PAGE_SIZE: u32 = 0x1000;
-const PAGE_TABLE_SIZE: usize = 256;
+pub const PAGE_TABLE_SIZE: usize = 256;
+// the ASID field of the CONTEXTIDR is 8 bits wide
+pub const ASID_COUNT: usize = 256;
 const L2_FAULT_PAGE_TABLE_ENTRY: u32 = 0x0;
 
 pub fn initialize() {
@@ -31,6 +33,66 @@ impl L2PageTable {
 #[no_mangle]
 static mut USED_PAGES: [bool; PAGE_TABLE_SIZE] = [false; PAGE_TABLE_SIZE];
 
+static mut DMA_PAGES: [bool; PAGE_TABLE_SIZE] = [false; PAGE_TABLE_SIZE];
+
+// Reserves physically contiguous pages, which are never handed out to tasks
+// until they are freed again. The pages lie in the identity mapped kernel
+// section, which is strongly ordered and therefore never cached.
+// Returns the physical address, the virtual address and the length in bytes.
+pub fn reserve_dma(pages: usize) -> Option<(u32, u32, u32)> {
+    if pages == 0 || pages > PAGE_TABLE_SIZE {
+        return None;
+    }
+
+    let start = (0..=PAGE_TABLE_SIZE - pages)
+        .find(|&start| (start..start + pages).all(|i| unsafe { !USED_PAGES[i] }))?;
+
+    for i in start..start + pages {
+        unsafe {
+            USED_PAGES[i] = true;
+            DMA_PAGES[i] = true;
+        }
+    }
+
+    let address = BASE_ADDRESS + ((start as u32) << PAGE_SIZE_BITS);
+
+    Some((address, address, pages as u32 * PAGE_SIZE))
+}
+
+pub fn free_dma(address: u32, pages: usize) {
+    if address < BASE_ADDRESS {
+        return;
+    }
+
+    let start = ((address - BASE_ADDRESS) >> PAGE_SIZE_BITS) as usize;
+
+    for i in start..(start + pages).min(PAGE_TABLE_SIZE) {
+        unsafe {
+            if DMA_PAGES[i] {
+                DMA_PAGES[i] = false;
+                USED_PAGES[i] = false;
+            }
+        }
+    }
+}
+
+// Removes whatever page is mapped at the virtual address
+pub fn clear_mapping(virtual_address: u32) {
+    unsafe {
+        LEVEL2_PAGE_TABLE.0[virtual_address as usize >> PAGE_SIZE_BITS] = L2_FAULT_PAGE_TABLE_ENTRY;
+
+        asm!("dsb", "isb");
+    }
+}
+
+// Returns the number of used pages and the number of pages in total
+pub fn page_usage() -> (usize, usize) {
+    let pages = &raw const USED_PAGES;
+    let used = unsafe { (*pages).iter() }.filter(|used| **used).count();
+
+    (used, PAGE_TABLE_SIZE)
+}
+
 pub struct L2SmallPageTableEntry {
     asid: Option<u32>,
     virtual_address: u32,
@@ -40,6 +102,14 @@ pub struct L2SmallPageTableEntry {
 
 impl L2SmallPageTableEntry {
     pub fn try_new(virtual_address: u32, asid: Option<u32>) -> Option<Self> {
+        Self::try_new_with_permissions(virtual_address, asid, AccessPermissions::Full)
+    }
+
+    pub fn try_new_with_permissions(
+        virtual_address: u32,
+        asid: Option<u32>,
+        permissions: AccessPermissions,
+    ) -> Option<Self> {
         let current_index =
             (0..PAGE_TABLE_SIZE as u32).find(|&i| unsafe { !USED_PAGES[i as usize] })?;
         unsafe {
@@ -51,7 +121,7 @@ impl L2SmallPageTableEntry {
             asid,
             virtual_address: virtual_address & !0xFFF,
             physical_address: BASE_ADDRESS + offset,
-            permissions: AccessPermissions::Full,
+            permissions,
         })
     }
 
@@ -80,7 +150,10 @@ impl L2SmallPageTableEntry {
 
     pub fn register(&self) {
         self.set_asid();
+        self.write_entry();
+    }
 
+    fn write_entry(&self) {
         unsafe {
             LEVEL2_PAGE_TABLE.0[self.virtual_address as usize >> PAGE_SIZE_BITS] = self.into();
 
@@ -89,9 +162,13 @@ impl L2SmallPageTableEntry {
     }
 
     pub fn unregister(&self) {
+        let index = self.virtual_address as usize >> PAGE_SIZE_BITS;
+
         unsafe {
-            LEVEL2_PAGE_TABLE.0[self.virtual_address as usize >> PAGE_SIZE_BITS] =
-                L2_FAULT_PAGE_TABLE_ENTRY;
+            // the slot may already map the page of another task
+            if LEVEL2_PAGE_TABLE.0[index] & !0xFFF == self.physical_address {
+                LEVEL2_PAGE_TABLE.0[index] = L2_FAULT_PAGE_TABLE_ENTRY;
+            }
             USED_PAGES[(self.physical_address - BASE_ADDRESS) as usize >> PAGE_SIZE_BITS] = false;
         }
 
@@ -102,6 +179,14 @@ impl L2SmallPageTableEntry {
         }
     }
 
+    pub fn set_permissions(&mut self, permissions: AccessPermissions) {
+        self.permissions = permissions;
+    }
+
+    pub fn physical_start(&self) -> u32 {
+        self.physical_address
+    }
+
     pub fn start(&self) -> u32 {
         self.virtual_address
     }
@@ -111,6 +196,51 @@ impl L2SmallPageTableEntry {
     }
 }
 
+// Changes the permissions of a task page. The shared page table only holds the
+// pages of the running task, so the entry is only rewritten if it belongs to
+// that task, other tasks pick it up once their pages are registered again.
+// The stale translation is dropped from the TLB in both cases.
+pub fn protect(entry: &mut L2SmallPageTableEntry, permissions: AccessPermissions) {
+    entry.set_permissions(permissions);
+
+    if entry.asid.is_none_or(|asid| asid == current_asid()) {
+        entry.write_entry();
+    }
+    entry.invalidate_tlb();
+
+    unsafe {
+        asm!("dsb", "isb");
+    }
+}
+
+fn current_asid() -> u32 {
+    let asid: u32;
+    unsafe {
+        asm!("mrc p15, 0, {asid}, c13, c0, 1", asid = out(reg) asid);
+    }
+
+    asid & 0xFF
+}
+
+impl fmt::Display for L2SmallPageTableEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#010x}-{:#010x} -> {:#010x}",
+            self.start(),
+            self.end(),
+            self.physical_address
+        )?;
+
+        match self.asid {
+            Some(asid) => write!(f, " asid {}", asid)?,
+            None => write!(f, " global")?,
+        }
+
+        write!(f, " {:?}", self.permissions)
+    }
+}
+
 impl From<&L2SmallPageTableEntry> for u32 {
     fn from(val: &L2SmallPageTableEntry) -> Self {
         let L2SmallPageTableEntry {
@@ -126,14 +256,30 @@ impl From<&L2SmallPageTableEntry> for u32 {
     }
 }
 
-enum AccessPermissions {
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AccessPermissions {
     Full,
+    // read only for privileged and user mode, never executable
+    ReadOnly,
+    // read only for privileged and user mode
+    ReadExecute,
+    // read write for privileged mode, read only for user mode
+    UserReadOnly,
+    // read write for privileged mode, no access for user mode
+    Privileged,
 }
 
+const AP2: u32 = 1 << 9;
+const EXECUTE_NEVER: u32 = 1 << 0;
+
 impl From<&AccessPermissions> for u32 {
     fn from(value: &AccessPermissions) -> Self {
         match value {
             AccessPermissions::Full => 0b11 << 4,
+            AccessPermissions::ReadOnly => AP2 | 0b11 << 4 | EXECUTE_NEVER,
+            AccessPermissions::ReadExecute => AP2 | 0b11 << 4,
+            AccessPermissions::UserReadOnly => 0b10 << 4,
+            AccessPermissions::Privileged => 0b01 << 4,
         }
     }
 }
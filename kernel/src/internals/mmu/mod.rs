@@ -2,5 +2,8 @@ mod l1;
 mod l2;
 mod setup;
 
-pub use l2::L2SmallPageTableEntry;
+pub use l2::{
+    clear_mapping, free_dma, page_usage, protect, reserve_dma, AccessPermissions,
+    L2SmallPageTableEntry, ASID_COUNT, PAGE_TABLE_SIZE,
+};
 pub use setup::initialize;
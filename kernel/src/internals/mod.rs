@@ -1,5 +1,8 @@
+pub mod barrier;
 pub mod clock;
 pub mod mmu;
+pub mod mutex;
+pub mod semaphore;
 pub mod sysclock;
 pub mod tasks;
 pub mod timer;
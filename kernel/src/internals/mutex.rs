@@ -0,0 +1,112 @@
+use shared::{
+    interrupts::CriticalSection,
+    kernel::{MUTEX_HELD, MUTEX_INVALID},
+};
+
+use super::tasks::{scheduler, Event};
+
+const MAX_MUTEXES: usize = 8;
+
+// returned to a task once it owns the mutex
+pub const MUTEX_LOCKED: u32 = 1;
+
+// Tasks waiting for the mutex are blocked on Event::Mutex with its id, so
+// they don't have to be tracked here
+struct Mutex {
+    owner: Option<usize>,
+    // the mutex is destroyed together with the task which created it
+    creator: usize,
+}
+
+// Why a mutex couldn't be locked, as returned to the task
+#[derive(Clone, Copy)]
+pub enum LockError {
+    // the task already holds the mutex and would wait forever
+    Held,
+    Invalid,
+}
+
+impl From<LockError> for u32 {
+    fn from(error: LockError) -> u32 {
+        match error {
+            LockError::Held => MUTEX_HELD,
+            LockError::Invalid => MUTEX_INVALID,
+        }
+    }
+}
+
+static MUTEXES: CriticalSection<[Option<Mutex>; MAX_MUTEXES]> =
+    CriticalSection::new([const { None }; MAX_MUTEXES]);
+
+pub fn create(creator: usize) -> Option<usize> {
+    let mut mutexes = MUTEXES.lock();
+    let id = mutexes.iter().position(|mutex| mutex.is_none())?;
+    mutexes[id] = Some(Mutex {
+        owner: None,
+        creator,
+    });
+
+    Some(id)
+}
+
+// Takes the mutex for the task if it's free, returns false if the task has to
+// wait
+pub fn try_lock(id: usize, task: usize) -> Result<bool, LockError> {
+    let mut mutexes = MUTEXES.lock();
+    let mutex = mutexes
+        .get_mut(id)
+        .and_then(Option::as_mut)
+        .ok_or(LockError::Invalid)?;
+
+    match mutex.owner {
+        None => {
+            mutex.owner = Some(task);
+            Ok(true)
+        }
+        Some(owner) if owner == task => Err(LockError::Held),
+        Some(_) => Ok(false),
+    }
+}
+
+// Hands the mutex over to the waiting task with the highest priority, returns
+// false if the task didn't own it
+pub fn unlock(id: usize, task: usize) -> bool {
+    let mut mutexes = MUTEXES.lock();
+    let Some(mutex) = mutexes.get_mut(id).and_then(Option::as_mut) else {
+        return false;
+    };
+
+    if mutex.owner != Some(task) {
+        return false;
+    }
+
+    mutex.owner = scheduler().wake_one(Event::Mutex(id), MUTEX_LOCKED);
+    true
+}
+
+// Frees the slot of a mutex the task created, tasks still waiting for it are
+// woken with MUTEX_INVALID
+pub fn destroy(id: usize, task: usize) -> bool {
+    let mut mutexes = MUTEXES.lock();
+    let Some(slot) = mutexes.get_mut(id) else {
+        return false;
+    };
+
+    if slot.as_ref().is_none_or(|mutex| mutex.creator != task) {
+        return false;
+    }
+
+    *slot = None;
+    scheduler().wake_all(Event::Mutex(id), MUTEX_INVALID);
+    true
+}
+
+// Unlocks every mutex held by a terminated task, so waiting tasks don't
+// block forever, and destroys the ones it created, so their slots can be
+// used again
+pub fn release_all(task: usize) {
+    for id in 0..MAX_MUTEXES {
+        unlock(id, task);
+        destroy(id, task);
+    }
+}
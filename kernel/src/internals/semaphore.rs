@@ -0,0 +1,88 @@
+use shared::{interrupts::CriticalSection, kernel::SEMAPHORE_INVALID};
+
+use super::tasks::{scheduler, Event};
+
+const MAX_SEMAPHORES: usize = 8;
+
+// returned to a task once it took a unit of the semaphore
+pub const SEMAPHORE_ACQUIRED: u32 = 1;
+
+// Waiting tasks are blocked on Event::Semaphore with its id, like the ones
+// waiting for a mutex
+struct Semaphore {
+    count: u32,
+    // the semaphore is destroyed together with the task which created it
+    creator: usize,
+}
+
+static SEMAPHORES: CriticalSection<[Option<Semaphore>; MAX_SEMAPHORES]> =
+    CriticalSection::new([const { None }; MAX_SEMAPHORES]);
+
+pub fn create(count: u32, creator: usize) -> Option<usize> {
+    let mut semaphores = SEMAPHORES.lock();
+    let id = semaphores
+        .iter()
+        .position(|semaphore| semaphore.is_none())?;
+    semaphores[id] = Some(Semaphore { count, creator });
+
+    Some(id)
+}
+
+// Takes a unit if one is available, returns false if the task has to wait
+pub fn try_wait(id: usize) -> Option<bool> {
+    let mut semaphores = SEMAPHORES.lock();
+    let semaphore = semaphores.get_mut(id)?.as_mut()?;
+
+    if semaphore.count == 0 {
+        return Some(false);
+    }
+
+    semaphore.count -= 1;
+    Some(true)
+}
+
+// Passes the unit straight to the waiting task with the highest priority, so
+// it can't be taken by another task before the waiter runs
+pub fn signal(id: usize) -> bool {
+    let mut semaphores = SEMAPHORES.lock();
+    let Some(semaphore) = semaphores.get_mut(id).and_then(Option::as_mut) else {
+        return false;
+    };
+
+    if scheduler()
+        .wake_one(Event::Semaphore(id), SEMAPHORE_ACQUIRED)
+        .is_none()
+    {
+        semaphore.count = semaphore.count.saturating_add(1);
+    }
+
+    true
+}
+
+// Frees the slot of a semaphore the task created, tasks still waiting for it
+// are woken with SEMAPHORE_INVALID
+pub fn destroy(id: usize, task: usize) -> bool {
+    let mut semaphores = SEMAPHORES.lock();
+    let Some(slot) = semaphores.get_mut(id) else {
+        return false;
+    };
+
+    if slot
+        .as_ref()
+        .is_none_or(|semaphore| semaphore.creator != task)
+    {
+        return false;
+    }
+
+    *slot = None;
+    scheduler().wake_all(Event::Semaphore(id), SEMAPHORE_INVALID);
+    true
+}
+
+// Destroys every semaphore created by a terminated task, so their slots can
+// be used again
+pub fn release_all(task: usize) {
+    for id in 0..MAX_SEMAPHORES {
+        destroy(id, task);
+    }
+}
@@ -1,15 +1,38 @@
-use super::timer::{self, DmTimer};
+use shared::interrupts;
+
+use super::{
+    tasks::scheduler,
+    timer::{self, DmTimer},
+};
+
+const TIMER: DmTimer = DmTimer::Timer2;
+
+// every tick of the timer is 31.25 us
+const TICKS_PER_MILLI: u32 = timer::TICKS_PER_MILLI;
+const RELOAD: u32 = u32::MAX - TICKS_PER_MILLI + 1;
 
 pub fn initialize() {
-    timer::register_timer(DmTimer::Timer2, 0xFFFF_FFE0, interrupt_handler);
+    timer::register_timer(TIMER, RELOAD, interrupt_handler);
+}
+
+// The timer is clocked and counting, so `micros` can be used
+pub fn running() -> bool {
+    timer::registered(TIMER)
 }
 
 static mut SYS_CLOCK: u32 = 0;
+// incremented every time SYS_CLOCK wraps, which happens after ~49 days
+static mut SYS_CLOCK_HIGH: u32 = 0;
 
 fn interrupt_handler() {
-    unsafe { SYS_CLOCK += 1 };
+    unsafe {
+        SYS_CLOCK = SYS_CLOCK.wrapping_add(1);
+        if SYS_CLOCK == 0 {
+            SYS_CLOCK_HIGH += 1;
+        }
+    };
 
-    if unsafe { SYS_CLOCK } % 10 == 0 {
+    if scheduler().quantum_expired() {
         unsafe { yield_task() };
     }
 }
@@ -18,6 +41,50 @@ pub fn millis() -> u32 {
     unsafe { SYS_CLOCK }
 }
 
+// Milliseconds since boot and the timer ticks into the current millisecond
+fn now() -> (u64, u32) {
+    interrupts::free(|| {
+        let mut millis = unsafe { (SYS_CLOCK_HIGH as u64) << 32 | SYS_CLOCK as u64 };
+        let mut ticks = timer::read_counter(TIMER) - RELOAD;
+
+        // the counter already reloaded, but the interrupt hasn't been handled yet
+        if timer::overflow_pending(TIMER) {
+            millis += 1;
+            ticks = timer::read_counter(TIMER) - RELOAD;
+        }
+
+        (millis, ticks)
+    })
+}
+
+pub fn micros() -> u32 {
+    micros64() as u32
+}
+
+// Microseconds since boot, which don't wrap for the lifetime of the system
+pub fn micros64() -> u64 {
+    let (millis, ticks) = now();
+    compose_micros(millis, ticks)
+}
+
+fn compose_micros(millis: u64, ticks: u32) -> u64 {
+    millis * 1000 + (ticks * 1000 / TICKS_PER_MILLI) as u64
+}
+
+// Counts raw timer ticks instead of relying on `millis`, as the tick interrupt
+// doesn't fire while we are inside a syscall
+pub fn delay_us(us: u32) {
+    let target = us.saturating_mul(TICKS_PER_MILLI).div_ceil(1000);
+    let mut elapsed = 0;
+    let mut last = timer::read_counter(TIMER);
+
+    while elapsed < target {
+        let now = timer::read_counter(TIMER);
+        elapsed += now.wrapping_sub(last) % TICKS_PER_MILLI;
+        last = now;
+    }
+}
+
 extern "C" {
     fn yield_task();
 }
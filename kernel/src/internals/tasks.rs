@@ -1,15 +1,51 @@
-use core::{cell::UnsafeCell, ptr};
-
-use shared::alloc::heap::BumpAllocator;
-
-use super::mmu::L2SmallPageTableEntry;
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    ptr,
+};
+
+use shared::{
+    alloc::heap::BumpAllocator,
+    collections::ArrayVec,
+    kernel::{
+        SchedulerStatus, TaskInfo, TaskStateTag, JOIN_EXITED, JOIN_NO_EXIT_CODE, MAX_TASK_NAME_LEN,
+    },
+};
+
+use super::{
+    barrier,
+    mmu::{self, AccessPermissions, L2SmallPageTableEntry},
+    mutex, semaphore,
+};
 use crate::sysclock::millis;
 
-const MAX_TASKS: usize = 4;
-const STACK_GUARD: usize = 1024;
-
+// Every task uses its id as ASID, so the ids must fit the ASID field, and a
+// task can't exist without at least one page
+const MAX_TASKS: usize = shared::kernel::MAX_TASKS;
+const _: () = assert!(MAX_TASKS <= mmu::ASID_COUNT);
+const _: () = assert!(MAX_TASKS <= mmu::PAGE_TABLE_SIZE);
+const DEFAULT_QUANTUM: u32 = 10;
+// failures in a row after which a service isn't restarted anymore
+const MAX_SERVICE_RESTARTS: u32 = 3;
+const MAX_NAME_LEN: usize = MAX_TASK_NAME_LEN;
+// lower numbers are scheduled first
+pub const DEFAULT_PRIORITY: u8 = 128;
+
+const PAGE_SIZE: usize = 0x1000;
+const MAX_CODE_PAGES: usize = 4;
+
+// the code is mapped read only to consecutive pages, followed by the heap page
+// and the stack page. The guard page between them is never mapped, so a stack
+// growing past its page raises a data abort instead of overwriting the heap.
 const CODE_PAGE_LOCATION: u32 = 0x0;
-const DATA_PAGE_LOCATION: u32 = 0x1000;
+const HEAP_PAGE_LOCATION: u32 = CODE_PAGE_LOCATION + (MAX_CODE_PAGES * PAGE_SIZE) as u32;
+const GUARD_PAGE_LOCATION: u32 = HEAP_PAGE_LOCATION + PAGE_SIZE as u32;
+const STACK_PAGE_LOCATION: u32 = GUARD_PAGE_LOCATION + PAGE_SIZE as u32;
+
+// user/boot/linker.ld ends every program with the length of its data, the
+// length of its data and bss and this magic
+const LAYOUT_MAGIC: u32 = 0x786E6566;
+const LAYOUT_LEN: usize = 12;
 
 #[derive(PartialEq)]
 pub enum TaskState {
@@ -17,9 +53,68 @@ pub enum TaskState {
     Running,
     Terminated,
     Waiting { until: u32 },
+    Sleeping { until: u32 },
+    Blocked(Event),
     Stored,
 }
 
+// Events a task can be blocked on, until another task or an interrupt
+// signals them
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    Barrier(usize),
+    // completion of the I2C transfer with the handle
+    I2cDone(u32),
+    // an edge on the pin, every waiting task is woken by it
+    GpioEdge { bank: usize, pin: u32 },
+    // termination of the task with the id
+    TaskExit(usize),
+    // release of the mutex with the id, which is handed to one waiting task
+    Mutex(usize),
+    // a unit of the semaphore with the id, which is passed to one waiting task
+    Semaphore(usize),
+}
+
+// Oneshot tasks run to completion and free their slot, services are loaded
+// again by the exit hook once they terminate
+#[derive(Clone, Copy, PartialEq)]
+pub enum TaskKind {
+    Oneshot,
+    Service,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExitReason {
+    Exit,
+    Panic,
+    // the task issued a syscall with an unknown number or malformed arguments
+    InvalidSyscall,
+    // the stack grew down into the guard page
+    StackOverflow,
+    // the task accessed memory it has no access to
+    DataAbort,
+    // another task terminated it
+    Killed,
+}
+
+impl ExitReason {
+    // The task ended because of a fault of its own
+    pub fn is_failure(self) -> bool {
+        !matches!(self, ExitReason::Exit | ExitReason::Killed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskCreationError {
+    // every task slot is taken
+    NoFreeSlot,
+    InvalidTask,
+    ProgramTooLarge,
+    OutOfPages,
+    // the layout at the end of the program doesn't fit the program
+    MalformedProgram,
+}
+
 pub struct TaskContext {
     pub sp: u32,
     pub pc: u32,
@@ -27,29 +122,58 @@ pub struct TaskContext {
 
 pub struct Task {
     id: usize,
+    name: [u8; MAX_NAME_LEN],
+    name_len: usize,
+    pub kind: TaskKind,
+    pub priority: u8,
+    code: &'static [u8],
     pub state: TaskState,
     pub context: TaskContext,
+    // written to r0 of the saved context, once the task gets resumed
+    pub resume_value: Option<u32>,
+    // written to r1, for the syscalls which return a second value
+    pub resume_extra: Option<u32>,
+    // milliseconds the task may run before it gets preempted
+    pub quantum: u32,
+    slice_start: u32,
+    // failures in a row of a service, which was restarted after each of them
+    restarts: u32,
+    // passed to Exit, None if the task was terminated otherwise
+    pub exit_code: Option<i32>,
     pub allocator: BumpAllocator,
-    code_page: L2SmallPageTableEntry,
-    data_page: L2SmallPageTableEntry,
+    code_pages: ArrayVec<L2SmallPageTableEntry, MAX_CODE_PAGES>,
+    heap_page: L2SmallPageTableEntry,
+    stack_page: L2SmallPageTableEntry,
 }
 
 impl Task {
     const fn empty() -> Self {
         Task {
             id: 0,
+            name: [0; MAX_NAME_LEN],
+            name_len: 0,
+            kind: TaskKind::Oneshot,
+            priority: DEFAULT_PRIORITY,
+            code: &[],
             state: TaskState::Terminated,
             context: TaskContext { sp: 0, pc: 0 },
+            resume_value: None,
+            resume_extra: None,
+            quantum: DEFAULT_QUANTUM,
+            slice_start: 0,
+            restarts: 0,
+            exit_code: None,
             allocator: BumpAllocator::new(),
-            code_page: L2SmallPageTableEntry::empty(),
-            data_page: L2SmallPageTableEntry::empty(),
+            code_pages: ArrayVec::new(),
+            heap_page: L2SmallPageTableEntry::empty(),
+            stack_page: L2SmallPageTableEntry::empty(),
         }
     }
 
     fn executable(&mut self) -> bool {
         match self.state {
             TaskState::Ready | TaskState::Stored => true,
-            TaskState::Waiting { until } => {
+            TaskState::Waiting { until } | TaskState::Sleeping { until } => {
                 if millis() >= until {
                     self.state = TaskState::Stored;
                     true
@@ -61,16 +185,136 @@ impl Task {
         }
     }
 
-    pub fn terminate(&mut self) {
+    // Names longer than MAX_NAME_LEN bytes are truncated
+    pub fn set_name(&mut self, name: &[u8]) {
+        let len = name.len().min(MAX_NAME_LEN);
+        self.name[..len].copy_from_slice(&name[..len]);
+        self.name_len = len;
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn name(&self) -> &[u8] {
+        &self.name[..self.name_len]
+    }
+
+    // None for terminated tasks, whose slot is free
+    pub fn info(&self) -> Option<TaskInfo> {
+        let state = match self.state {
+            TaskState::Terminated => return None,
+            TaskState::Ready | TaskState::Stored => TaskStateTag::Ready,
+            TaskState::Running => TaskStateTag::Running,
+            TaskState::Waiting { .. } | TaskState::Sleeping { .. } => TaskStateTag::Sleeping,
+            TaskState::Blocked(_) => TaskStateTag::Blocked,
+        };
+
+        Some(TaskInfo::new(
+            self.id as u32,
+            state,
+            self.priority,
+            self.name(),
+        ))
+    }
+
+    pub fn set_quantum(&mut self, quantum: u32) {
+        self.quantum = match quantum {
+            0 => DEFAULT_QUANTUM,
+            quantum => quantum,
+        };
+    }
+
+    // Tells whether `len` bytes at `address` lie within the task's code, heap
+    // or stack page
+    pub fn owns(&self, address: u32, len: u32) -> bool {
+        let Some(end) = address.checked_add(len) else {
+            return false;
+        };
+
+        // the code pages start at address 0
+        let code_end = CODE_PAGE_LOCATION + (self.code_pages.len() * PAGE_SIZE) as u32;
+        end <= code_end || self.owns_writable(address, len)
+    }
+
+    // Like `owns`, but only for the pages the task may write to, the data and
+    // bss of a program live on its heap page
+    pub fn owns_writable(&self, address: u32, len: u32) -> bool {
+        let Some(end) = address.checked_add(len) else {
+            return false;
+        };
+
+        let within = |start: u32| address >= start && end <= start + PAGE_SIZE as u32;
+        within(HEAP_PAGE_LOCATION) || within(STACK_PAGE_LOCATION)
+    }
+
+    pub fn alloc(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.allocator.alloc(layout) }
+    }
+
+    // A stack pointer below its page points into the guard page, the next
+    // push raises a data abort
+    pub fn stack_overflowed(&self, sp: u32) -> bool {
+        sp < self.stack_page.start()
+    }
+
+    // Terminates the task without running the exit hook and frees its pages
+    fn release(&mut self) {
         self.state = TaskState::Terminated;
-        self.data_page.unregister();
-        self.code_page.unregister();
+        self.heap_page.unregister();
+        self.stack_page.unregister();
+        release_pages(&mut self.code_pages);
+    }
+
+    fn register_pages(&self) {
+        for page in self.code_pages.iter() {
+            page.register();
+        }
+        self.heap_page.register();
+        self.stack_page.register();
+
+        // a previous task may have used more code pages
+        for page in self.code_pages.len()..MAX_CODE_PAGES {
+            mmu::clear_mapping(CODE_PAGE_LOCATION + (page * PAGE_SIZE) as u32);
+        }
+    }
+
+    // The guard page is at the same address in every task
+    pub fn in_guard_page(address: u32) -> bool {
+        (GUARD_PAGE_LOCATION..STACK_PAGE_LOCATION).contains(&address)
+    }
+
+    pub fn dump_pages(&self) {
+        for page in self.code_pages.iter() {
+            crate::println!("task {} code {}", self.id, page);
+        }
+        crate::println!("task {} heap {}", self.id, self.heap_page);
+        crate::println!("task {} stack {}", self.id, self.stack_page);
+    }
+
+    // Status and exit code as returned to joining tasks, the code is only
+    // meaningful for JOIN_EXITED
+    pub fn join_result(&self) -> (u32, u32) {
+        self.exit_code
+            .map_or((JOIN_NO_EXIT_CODE, 0), |code| (JOIN_EXITED, code as u32))
+    }
+
+    // Returns the event joining tasks are woken with and the status and code
+    // they get, the scheduler wakes them once the task isn't borrowed anymore
+    fn terminate(&mut self) -> (Event, (u32, u32)) {
+        self.release();
+
+        (Event::TaskExit(self.id), self.join_result())
     }
 }
 
 pub struct Scheduler {
     tasks: [UnsafeCell<Task>; MAX_TASKS],
-    pub current_index: Option<usize>,
+    // only set by `switch` to the task it starts, cleared by `cycle`
+    current_index: Option<usize>,
+    // where the round robin search for the next task starts
+    next_index: usize,
+    pub status: SchedulerStatus,
 }
 
 impl Scheduler {
@@ -78,6 +322,8 @@ impl Scheduler {
         Scheduler {
             tasks: [const { UnsafeCell::new(Task::empty()) }; MAX_TASKS],
             current_index: None,
+            next_index: 0,
+            status: SchedulerStatus::Running,
         }
     }
 
@@ -88,7 +334,6 @@ impl Scheduler {
         }
     }
 
-    #[allow(dead_code)]
     fn task(&self, index: usize) -> &Task {
         unsafe { &*self.tasks[index].get() }
     }
@@ -99,19 +344,146 @@ impl Scheduler {
     }
 
     pub fn current(&mut self) -> Option<&mut Task> {
-        self.current_index
+        self.current_task_id()
             .map(move |index| self.task_mut(index))
-            .filter(|task| task.state == TaskState::Running)
     }
 
+    // Id of the running task, None while the kernel has switched away from it
+    pub fn current_task_id(&self) -> Option<usize> {
+        self.current_index
+            .filter(|&index| self.task(index).state == TaskState::Running)
+    }
+
+    // Join status and exit code of a task, JOIN_NO_EXIT_CODE if there is no
+    // such task
+    pub fn join_result(&self, id: usize) -> (u32, u32) {
+        if id >= MAX_TASKS {
+            return (JOIN_NO_EXIT_CODE, 0);
+        }
+
+        self.task(id).join_result()
+    }
+
+    // Fills `tasks` with the live tasks in the order of their ids and returns
+    // how many were written. Only reads the tasks, so it can be called while
+    // one of them is running.
+    pub fn task_info(&self, tasks: &mut [TaskInfo]) -> usize {
+        let live = (0..MAX_TASKS).filter_map(|index| self.task(index).info());
+
+        let mut count = 0;
+        for (slot, info) in tasks.iter_mut().zip(live) {
+            *slot = info;
+            count += 1;
+        }
+
+        count
+    }
+
+    // Other tasks are woken and the exit hook runs once the task isn't
+    // borrowed anymore, as the hook may load a program into the same slot
+    pub fn terminate(&mut self, id: usize, reason: ExitReason) {
+        let (event, (status, code)) = self.task_mut(id).terminate();
+
+        mutex::release_all(id);
+        semaphore::release_all(id);
+        barrier::release_all(id);
+        self.wake_all_with_extra(event, status, Some(code));
+
+        unsafe { EXIT_HOOK(id, reason) };
+    }
+
+    // Whether the task exists and hasn't terminated yet
+    pub fn alive(&self, id: usize) -> bool {
+        id < MAX_TASKS && self.task_mut(id).state != TaskState::Terminated
+    }
+
+    // Ends the cancelable sleep of a task early, returns whether it was asleep
+    pub fn wake(&mut self, id: usize) -> bool {
+        if id >= MAX_TASKS {
+            return false;
+        }
+
+        let task = self.task_mut(id);
+        if !matches!(task.state, TaskState::Sleeping { .. }) {
+            return false;
+        }
+
+        task.state = TaskState::Stored;
+        task.resume_value = Some(true as u32);
+        true
+    }
+
+    // Resumes every task blocked on the event with the given return value,
+    // returns how many tasks were resumed
+    pub fn wake_all(&mut self, event: Event, value: u32) -> usize {
+        self.wake_all_with_extra(event, value, None)
+    }
+
+    // Like `wake_all`, the tasks get `extra` as second return value in r1
+    pub fn wake_all_with_extra(&mut self, event: Event, value: u32, extra: Option<u32>) -> usize {
+        let mut woken = 0;
+
+        for index in 0..MAX_TASKS {
+            let task = self.task_mut(index);
+            if task.state == TaskState::Blocked(event) {
+                task.state = TaskState::Stored;
+                task.resume_value = Some(value);
+                task.resume_extra = extra;
+                woken += 1;
+            }
+        }
+
+        woken
+    }
+
+    // Resumes the task with the highest priority blocked on the event and
+    // returns its id
+    pub fn wake_one(&mut self, event: Event, value: u32) -> Option<usize> {
+        let index = (0..MAX_TASKS)
+            .filter(|&index| self.task(index).state == TaskState::Blocked(event))
+            .min_by_key(|&index| self.task(index).priority)?;
+
+        let task = self.task_mut(index);
+        task.state = TaskState::Stored;
+        task.resume_value = Some(value);
+
+        Some(index)
+    }
+
+    // Whether any task besides the current one could run right now
+    pub fn runnable_peer(&mut self) -> bool {
+        let priority = self.current().map(|task| task.priority).unwrap_or(u8::MAX);
+
+        (0..MAX_TASKS)
+            .filter(|&index| Some(index) != self.current_task_id())
+            .any(|index| {
+                let task = self.task_mut(index);
+                task.priority <= priority && task.executable()
+            })
+    }
+
+    pub fn live_tasks(&self) -> usize {
+        (0..MAX_TASKS)
+            .filter(|&index| self.task(index).state != TaskState::Terminated)
+            .count()
+    }
+
+    pub fn quantum_expired(&mut self) -> bool {
+        match self.current() {
+            Some(task) => millis().wrapping_sub(task.slice_start) >= task.quantum,
+            None => false,
+        }
+    }
+
+    // Gives up the current task, the search for the next one starts after it
     pub fn cycle(&mut self) {
-        if let Some(ref mut index) = self.current_index {
-            *index = (*index + 1) % MAX_TASKS;
+        if let Some(index) = self.current_index.take() {
+            self.next_index = (index + 1) % MAX_TASKS;
         }
     }
 
     fn task_with_state(&self, state: TaskState) -> Option<&mut Task> {
-        let initial_index = self.current_index.unwrap_or(0);
+        let initial_index = self.next_index;
         let mut index = initial_index;
 
         loop {
@@ -129,14 +501,22 @@ impl Scheduler {
         None
     }
 
+    // Picks the executable task with the highest priority. Tasks of the same
+    // priority are taken in turns, as the search starts after the last task.
     fn next_task(&mut self) -> Option<&mut Task> {
-        let initial_index = self.current_index.unwrap_or(0);
+        let initial_index = self.next_index;
         let mut index = initial_index;
+        let mut next: Option<usize> = None;
 
         loop {
             let current_task = self.task_mut(index);
-            if current_task.executable() {
-                return Some(current_task);
+            let higher = match next {
+                Some(next) => current_task.priority < self.task(next).priority,
+                None => true,
+            };
+
+            if higher && current_task.executable() {
+                next = Some(index);
             }
 
             index = (index + 1) % MAX_TASKS;
@@ -145,54 +525,194 @@ impl Scheduler {
             }
         }
 
-        None
+        next.map(|index| self.task_mut(index))
+    }
+
+    pub fn create_task(
+        &mut self,
+        name: &[u8],
+        code: &'static [u8],
+        kind: TaskKind,
+        priority: u8,
+    ) -> Result<usize, TaskCreationError> {
+        let task_id = self
+            .task_with_state(TaskState::Terminated)
+            .ok_or(TaskCreationError::NoFreeSlot)?
+            .id;
+
+        self.load(task_id, code, kind)?;
+
+        let task = self.task_mut(task_id);
+        task.set_name(name);
+        task.priority = priority;
+        task.restarts = 0;
+
+        Ok(task_id)
+    }
+
+    // Loads the program of a terminated service again into its old slot. A
+    // service which failed MAX_SERVICE_RESTARTS times in a row stays
+    // terminated, e.g. one which panics right after it starts.
+    pub fn respawn(&mut self, id: usize, reason: ExitReason) -> bool {
+        if id >= MAX_TASKS {
+            return false;
+        }
+
+        let task = self.task_mut(id);
+        if task.state != TaskState::Terminated || task.kind != TaskKind::Service {
+            return false;
+        }
+
+        task.restarts = if reason.is_failure() {
+            task.restarts + 1
+        } else {
+            0
+        };
+        if task.restarts > MAX_SERVICE_RESTARTS {
+            return false;
+        }
+
+        self.load(id, task.code, TaskKind::Service).is_ok()
+    }
+
+    // Swaps the program of a task and restarts it at the new entry. The old
+    // pages are freed before the new ones are allocated, so they get reused.
+    pub fn replace_task(
+        &mut self,
+        id: usize,
+        code: &'static [u8],
+    ) -> Result<(), TaskCreationError> {
+        if id >= MAX_TASKS {
+            return Err(TaskCreationError::InvalidTask);
+        }
+
+        // checked up front, so the old program keeps running if it doesn't fit
+        code_pages_for(program_layout(code)?.code)?;
+
+        let task = self.task_mut(id);
+        let kind = task.kind;
+        if task.state != TaskState::Terminated {
+            task.release();
+        }
+
+        self.load(id, code, kind)?;
+        self.task_mut(id).restarts = 0;
+
+        Ok(())
     }
 
-    pub fn create_task(&mut self, code: &[u8]) -> Option<usize> {
-        let task_id = self.task_with_state(TaskState::Terminated)?.id;
+    fn load(
+        &mut self,
+        task_id: usize,
+        code: &'static [u8],
+        kind: TaskKind,
+    ) -> Result<usize, TaskCreationError> {
+        // checked before any page is allocated, so nothing has to be rolled
+        // back and no copy can run past the last page
+        let layout = program_layout(code)?;
+        let pages = code_pages_for(layout.code)?;
+
+        let asid = Some(task_id as u32);
+        let mut code_pages = ArrayVec::new();
+        for page in 0..pages {
+            let location = CODE_PAGE_LOCATION + (page * PAGE_SIZE) as u32;
+
+            // roll back the pages allocated so far
+            let Some(entry) = L2SmallPageTableEntry::try_new_with_permissions(
+                location,
+                asid,
+                AccessPermissions::UserReadOnly,
+            ) else {
+                release_pages(&mut code_pages);
+                return Err(TaskCreationError::OutOfPages);
+            };
+            let _ = code_pages.push(entry);
+        }
+
+        let Some(heap_page) = L2SmallPageTableEntry::try_new(HEAP_PAGE_LOCATION, asid) else {
+            release_pages(&mut code_pages);
+            return Err(TaskCreationError::OutOfPages);
+        };
 
-        let code_page = L2SmallPageTableEntry::try_new(CODE_PAGE_LOCATION, Some(task_id as u32))?;
-        let data_page = L2SmallPageTableEntry::try_new(DATA_PAGE_LOCATION, Some(task_id as u32))?;
+        let Some(stack_page) = L2SmallPageTableEntry::try_new(STACK_PAGE_LOCATION, asid) else {
+            heap_page.unregister();
+            release_pages(&mut code_pages);
+            return Err(TaskCreationError::OutOfPages);
+        };
 
-        let dest = code_page.start() as *mut u8;
-        unsafe {
-            ptr::copy_nonoverlapping(code.as_ptr(), dest, code.len());
+        // the pages aren't mapped yet, so the program is copied through their
+        // identity mapped physical addresses. The heap page starts with the
+        // data, the rest of it is zeroed, as it holds the bss.
+        for (index, page) in code_pages.iter().enumerate() {
+            let chunk = layout.code.chunks(PAGE_SIZE).nth(index).unwrap_or(&[]);
+            copy_to_page(page, chunk);
         }
+        copy_to_page(&heap_page, layout.data);
 
         let task = self.task_mut(task_id);
-        task.code_page = code_page;
-        task.data_page = data_page;
+        task.kind = kind;
+        task.code = code;
+        task.code_pages = code_pages;
+        task.heap_page = heap_page;
+        task.stack_page = stack_page;
         task.state = TaskState::Ready;
-        task.context.sp = task.data_page.end();
-        task.context.pc = task.code_page.start();
-        task.allocator
-            .init(task.data_page.start() as usize, task.data_page.end() as usize - STACK_GUARD);
-        Some(task.id)
+        task.resume_value = None;
+        task.resume_extra = None;
+        task.exit_code = None;
+        task.quantum = DEFAULT_QUANTUM;
+        task.context.sp = task.stack_page.end();
+        task.context.pc = CODE_PAGE_LOCATION;
+        task.allocator.init(
+            task.heap_page.start() as usize + layout.static_len,
+            task.heap_page.start() as usize + PAGE_SIZE,
+        );
+        Ok(task.id)
     }
 
     pub fn switch(&mut self) {
         let next_task_id = match self.next_task() {
             Some(task) => task.id,
-            None => return,
+            None => {
+                self.status = match self.live_tasks() {
+                    0 => SchedulerStatus::AllTerminated,
+                    _ => SchedulerStatus::AllWaiting,
+                };
+                return;
+            }
         };
 
+        // next_task only returns executable tasks, which are started below
+        if !matches!(
+            self.task(next_task_id).state,
+            TaskState::Ready | TaskState::Stored
+        ) {
+            return;
+        }
         self.current_index = Some(next_task_id);
 
         let task = self.task_mut(next_task_id);
+        task.slice_start = millis();
 
         match task.state {
             TaskState::Ready => {
                 task.state = TaskState::Running;
-                task.code_page.register();
-                task.data_page.register();
+                task.register_pages();
                 unsafe {
                     switch_context(task.context.sp, task.context.pc);
                 }
             }
             TaskState::Stored => {
                 task.state = TaskState::Running;
-                task.code_page.register();
-                task.data_page.register();
+                task.register_pages();
+
+                // the saved context starts with the cpsr, followed by r0-r12 and lr
+                if let Some(value) = task.resume_value.take() {
+                    unsafe { ptr::write_volatile((task.context.sp + 4) as *mut u32, value) };
+                }
+                if let Some(extra) = task.resume_extra.take() {
+                    unsafe { ptr::write_volatile((task.context.sp + 8) as *mut u32, extra) };
+                }
+
                 unsafe {
                     restore_context(task.context.sp, task.context.pc);
                 }
@@ -202,8 +722,85 @@ impl Scheduler {
     }
 }
 
+// The code of a program and the data which is copied to its heap page
+struct ProgramLayout {
+    code: &'static [u8],
+    data: &'static [u8],
+    // length of the data and bss, the heap starts behind them
+    static_len: usize,
+}
+
+// Programs without the layout at their end have neither data nor bss
+fn program_layout(program: &'static [u8]) -> Result<ProgramLayout, TaskCreationError> {
+    let no_data = ProgramLayout {
+        code: program,
+        data: &[],
+        static_len: 0,
+    };
+
+    let Some(layout_start) = program.len().checked_sub(LAYOUT_LEN) else {
+        return Ok(no_data);
+    };
+    let word = |index: usize| {
+        let start = layout_start + index * 4;
+        u32::from_le_bytes(program[start..start + 4].try_into().unwrap()) as usize
+    };
+
+    if word(2) != LAYOUT_MAGIC as usize {
+        return Ok(no_data);
+    }
+
+    let (data_len, static_len) = (word(0), word(1));
+    let code_len = layout_start
+        .checked_sub(data_len)
+        .ok_or(TaskCreationError::MalformedProgram)?;
+    if data_len > static_len {
+        return Err(TaskCreationError::MalformedProgram);
+    }
+    if static_len > PAGE_SIZE {
+        return Err(TaskCreationError::ProgramTooLarge);
+    }
+
+    Ok(ProgramLayout {
+        code: &program[..code_len],
+        data: &program[code_len..layout_start],
+        static_len,
+    })
+}
+
+// Copies the bytes to the start of the page and zeroes the rest of it
+fn copy_to_page(page: &L2SmallPageTableEntry, bytes: &[u8]) {
+    let dest = page.physical_start() as *mut u8;
+
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), dest, bytes.len());
+        ptr::write_bytes(dest.add(bytes.len()), 0, PAGE_SIZE - bytes.len());
+    }
+}
+
+// Number of code pages the program needs, the heap and the stack page come
+// on top of them
+fn code_pages_for(code: &[u8]) -> Result<usize, TaskCreationError> {
+    let pages = code.len().div_ceil(PAGE_SIZE).max(1);
+    if pages > MAX_CODE_PAGES {
+        return Err(TaskCreationError::ProgramTooLarge);
+    }
+
+    Ok(pages)
+}
+
+fn release_pages(pages: &mut ArrayVec<L2SmallPageTableEntry, MAX_CODE_PAGES>) {
+    while let Some(page) = pages.pop() {
+        page.unregister();
+    }
+}
+
 static mut SCHEDULER: Scheduler = Scheduler::new();
 
+static mut EXIT_HOOK: fn(usize, ExitReason) = default_exit_hook;
+
+fn default_exit_hook(_id: usize, _reason: ExitReason) {}
+
 #[allow(static_mut_refs)]
 pub fn scheduler() -> &'static mut Scheduler {
     unsafe { &mut SCHEDULER }
@@ -214,9 +811,30 @@ pub fn init() {
     scheduler.init();
 }
 
-pub fn create_task(code: &[u8]) -> Option<usize> {
+pub fn create_task(
+    name: &[u8],
+    code: &'static [u8],
+    kind: TaskKind,
+    priority: u8,
+) -> Result<usize, TaskCreationError> {
+    let scheduler = scheduler();
+    scheduler.create_task(name, code, kind, priority)
+}
+
+pub fn replace_task(id: usize, code: &'static [u8]) -> Result<(), TaskCreationError> {
     let scheduler = scheduler();
-    scheduler.create_task(code)
+    scheduler.replace_task(id, code)
+}
+
+// Exit hook, which restarts every service once it terminated, unless it keeps
+// failing
+pub fn restart_services(id: usize, reason: ExitReason) {
+    scheduler().respawn(id, reason);
+}
+
+// Called with the id of every task that terminates, e.g. to log or respawn it
+pub fn set_exit_hook(hook: fn(usize, ExitReason)) {
+    unsafe { EXIT_HOOK = hook };
 }
 
 extern "C" {
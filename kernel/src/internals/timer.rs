@@ -1,21 +1,76 @@
 use crate::interrupts::{self, Interrupt};
-use shared::sys::write_addr;
+use shared::sys::{read_addr, read_bit, write_addr};
 
 use super::clock::FuncClock;
 
+const TIMER_IRQSTATUS_RAW: u32 = 0x24;
 const TIMER_IRQSTATUS: u32 = 0x28;
 const TIMER_IRQENABLE_SET: u32 = 0x2C;
+const TIMER_IRQENABLE_CLR: u32 = 0x30;
 const TIMER_CONTROL: u32 = 0x38;
 const TIMER_COUNTER: u32 = 0x3C;
 const TIMER_LOAD: u32 = 0x40;
 
+// The timers are clocked at 32 kHz
+pub const TICKS_PER_MILLI: u32 = 32;
+
 static mut TIMERS: &mut [Option<Timer>; 6] = &mut [const { None }; 6];
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimerMode {
+    // reloads on every overflow and fires until the timer is replaced
+    Periodic,
+    // stops after the first overflow, so the handler only runs once
+    OneShot,
+}
+
 pub fn register_timer(dm_timer: DmTimer, reload: u32, handler: fn()) {
-    let timer = Timer::new(dm_timer, reload, handler);
+    register_timer_mode(dm_timer, reload, TimerMode::Periodic, handler);
+}
+
+pub fn register_timer_mode(dm_timer: DmTimer, reload: u32, mode: TimerMode, handler: fn()) {
+    let timer = Timer::new(dm_timer, reload, mode, handler);
     unsafe { TIMERS[dm_timer as usize] = Some(timer) }
 }
 
+// Reload value, which lets the timer overflow every `ms` milliseconds
+pub const fn reload_for_ms(ms: u32) -> Option<u32> {
+    match ms.checked_mul(TICKS_PER_MILLI) {
+        Some(0) | None => None,
+        Some(ticks) => Some(0u32.wrapping_sub(ticks)),
+    }
+}
+
+// Changes the period of a registered timer, which takes effect on its next
+// reload. Returns false if the timer isn't registered or the period doesn't fit.
+pub fn set_period_ms(dm_timer: DmTimer, ms: u32) -> bool {
+    let reload = match reload_for_ms(ms) {
+        Some(reload) => reload,
+        None => return false,
+    };
+
+    match unsafe { &mut TIMERS[dm_timer as usize] } {
+        Some(timer) => {
+            timer.reload = reload;
+            write_addr(dm_timer.address() + TIMER_LOAD, reload);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn registered(dm_timer: DmTimer) -> bool {
+    unsafe { TIMERS[dm_timer as usize].is_some() }
+}
+
+pub fn read_counter(dm_timer: DmTimer) -> u32 {
+    read_addr(dm_timer.address() + TIMER_COUNTER)
+}
+
+pub fn overflow_pending(dm_timer: DmTimer) -> bool {
+    read_bit(dm_timer.address() + TIMER_IRQSTATUS_RAW, 1)
+}
+
 pub fn get_timer(interrupt: Interrupt) -> &'static Option<Timer> {
     let dm_timer = match DmTimer::try_new(interrupt) {
         Some(dm_timer) => dm_timer,
@@ -28,14 +83,16 @@ pub fn get_timer(interrupt: Interrupt) -> &'static Option<Timer> {
 pub struct Timer {
     timer: DmTimer,
     reload: u32,
+    mode: TimerMode,
     handler: fn(),
 }
 
 impl Timer {
-    fn new(timer: DmTimer, reload: u32, handler: fn()) -> Self {
+    fn new(timer: DmTimer, reload: u32, mode: TimerMode, handler: fn()) -> Self {
         let timer = Timer {
             timer,
             reload,
+            mode,
             handler,
         };
 
@@ -60,18 +117,32 @@ impl Timer {
     fn init_interrupt(&self) {
         self.irq_enable();
 
-        interrupts::register_handler(Self::handle_timer_irq, self.timer.interrupt());
-        interrupts::enable_interrupt(self.timer.interrupt(), interrupts::Mode::IRQ, 0);
+        interrupts::enable_interrupt(
+            self.timer.interrupt(),
+            interrupts::Mode::IRQ,
+            0,
+            Self::handle_timer_irq,
+        );
     }
 
     fn start(&self) {
-        write_addr(self.timer.address() + TIMER_CONTROL, 0x3);
+        // bit 0 starts the timer, bit 1 enables the auto reload
+        let control = match self.mode {
+            TimerMode::Periodic => 0x3,
+            TimerMode::OneShot => 0x1,
+        };
+
+        write_addr(self.timer.address() + TIMER_CONTROL, control);
     }
 
     fn irq_enable(&self) {
         write_addr(self.timer.address() + TIMER_IRQENABLE_SET, 0x2);
     }
 
+    fn irq_disable(&self) {
+        write_addr(self.timer.address() + TIMER_IRQENABLE_CLR, 0x2);
+    }
+
     fn irq_acknowledge(&self) {
         write_addr(self.timer.address() + TIMER_IRQSTATUS, 0x2);
     }
@@ -84,6 +155,12 @@ impl Timer {
 
             if let Some(timer) = timer {
                 timer.irq_acknowledge();
+
+                // the counter already stopped, so it never overflows again
+                if timer.mode == TimerMode::OneShot {
+                    timer.irq_disable();
+                }
+
                 (timer.handler)();
             }
         }
@@ -1,21 +1,81 @@
 use shared::sys::{noop, read_addr, set_bit, write_addr};
 
+use crate::internals::sysclock;
+
 const INTC: u32 = 0x48200000;
 
 const INTC_ILR: u32 = 0x100;
 const INTC_SIR_IRQ: u32 = 0x40;
+const INTC_SIR_FIQ: u32 = 0x44;
 const INTC_CONTROL: u32 = 0x48;
+const INTC_THRESHOLD: u32 = 0x68;
+
+// threshold value which lets every priority through, the reset value
+pub const NO_THRESHOLD: u8 = 0xFF;
 
 static mut INTERRUPT_HANDLERS: &mut [fn(); 128] = &mut [noop; 128];
+static mut FIQ_HANDLERS: &mut [fn(); 128] = &mut [noop; 128];
+
+// Timestamps in microseconds, only taken in debug builds
+static mut IRQ_ENTRY: u32 = 0;
+static mut LAST_LATENCY: u32 = 0;
+static mut MAX_LATENCY: u32 = 0;
+
+// Called by the assembly entry of the irq handler
+#[no_mangle]
+extern "C" fn record_irq_entry() {
+    if cfg!(debug_assertions) && sysclock::running() {
+        unsafe { IRQ_ENTRY = sysclock::micros() };
+    }
+}
+
+fn record_dispatch() {
+    if cfg!(debug_assertions) && sysclock::running() {
+        let latency = latency_us(unsafe { IRQ_ENTRY }, sysclock::micros());
+
+        unsafe {
+            LAST_LATENCY = latency;
+            MAX_LATENCY = MAX_LATENCY.max(latency);
+        }
+    }
+}
+
+// The microsecond clock wraps, so the difference is taken modulo 2^32
+pub fn latency_us(entry: u32, dispatch: u32) -> u32 {
+    dispatch.wrapping_sub(entry)
+}
+
+pub fn last_latency_us() -> u32 {
+    unsafe { LAST_LATENCY }
+}
+
+pub fn max_latency_us() -> u32 {
+    unsafe { MAX_LATENCY }
+}
 
 #[no_mangle]
 fn handle_interrupt() {
     let interrupt = current();
+    record_dispatch();
     execute(interrupt);
     clear();
 }
 
-pub fn enable_interrupt(interrupt: Interrupt, mode: Mode, priority: u8) {
+// Called by the assembly entry of the fiq handler. FIQ mode masks IRQs and has
+// its own r8-r12, sp and lr, so it preempts any irq handler without touching
+// its registers. CriticalSection only masks IRQs though, so fiq handlers must
+// not lock one and may only share data through atomics, e.g. a RingBuffer.
+#[no_mangle]
+extern "C" fn fiq_handler() {
+    if let Some(interrupt) = current_fiq() {
+        unsafe { FIQ_HANDLERS[interrupt as usize]() }
+    }
+    clear_fiq();
+}
+
+// The handler is put into the table of the mode, before the interrupt is
+// unmasked, so it can't fire into the table of the other mode
+pub fn enable_interrupt(interrupt: Interrupt, mode: Mode, priority: u8, handler: fn()) {
     let interrupt_number = interrupt as u32;
 
     let addr = INTC + INTC_ILR + (4 * interrupt_number);
@@ -28,14 +88,37 @@ pub fn enable_interrupt(interrupt: Interrupt, mode: Mode, priority: u8) {
         None => return,
     };
 
+    unsafe {
+        let (handlers, other) = match mode {
+            Mode::IRQ => (&raw mut INTERRUPT_HANDLERS, &raw mut FIQ_HANDLERS),
+            Mode::FIQ => (&raw mut FIQ_HANDLERS, &raw mut INTERRUPT_HANDLERS),
+        };
+        (*handlers)[interrupt as usize] = handler;
+        (*other)[interrupt as usize] = noop;
+    }
+
     write_addr(addr, enable_fiq | (priority << 2) as u32);
     set_bit(INTC + bank.get_mir() + 4, interrupt_number % 32);
 }
 
-pub fn register_handler(handler: fn(), interrupt: Interrupt) {
-    unsafe {
-        INTERRUPT_HANDLERS[interrupt as usize] = handler;
-    }
+// Only interrupts with a lower priority number than the threshold are
+// delivered, the others stay pending until it's raised again. Returns the
+// previous threshold.
+pub fn set_threshold(priority: u8) -> u8 {
+    let previous = read_addr(INTC + INTC_THRESHOLD) & 0xFF;
+    write_addr(INTC + INTC_THRESHOLD, priority as u32);
+
+    previous as u8
+}
+
+pub fn with_threshold<F, T>(priority: u8, f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let previous = set_threshold(priority);
+    let result = f();
+    set_threshold(previous);
+    result
 }
 
 pub fn current() -> Option<Interrupt> {
@@ -44,6 +127,12 @@ pub fn current() -> Option<Interrupt> {
     Interrupt::new(num)
 }
 
+pub fn current_fiq() -> Option<Interrupt> {
+    let num = read_addr(INTC + INTC_SIR_FIQ) & 0x7F;
+
+    Interrupt::new(num)
+}
+
 pub fn execute(interrupt: Option<Interrupt>) {
     if let Some(interrupt) = interrupt {
         unsafe { INTERRUPT_HANDLERS[interrupt as usize]() }
@@ -54,14 +143,25 @@ pub fn clear() {
     write_addr(INTC + INTC_CONTROL, 0x1);
 }
 
+// NEWFIQAGR, lets the controller deliver the next fiq
+pub fn clear_fiq() {
+    write_addr(INTC + INTC_CONTROL, 0x2);
+}
+
+#[derive(Clone, Copy)]
 pub enum Interrupt {
     I2C2INT = 30,
+    GPIOINT2A = 32,
+    GPIOINT3A = 62,
     TINT2 = 68,
     TINT3 = 69,
+    I2C0INT = 70,
+    I2C1INT = 71,
     TINT4 = 92,
     TINT5 = 93,
     TINT6 = 94,
     TINT7 = 95,
+    GPIOINT0A = 96,
     GPIOINT1A = 98,
 }
 
@@ -69,12 +169,17 @@ impl Interrupt {
     pub fn new(num: u32) -> Option<Self> {
         match num {
             30 => Some(Interrupt::I2C2INT),
+            32 => Some(Interrupt::GPIOINT2A),
+            62 => Some(Interrupt::GPIOINT3A),
             68 => Some(Interrupt::TINT2),
             69 => Some(Interrupt::TINT3),
+            70 => Some(Interrupt::I2C0INT),
+            71 => Some(Interrupt::I2C1INT),
             92 => Some(Interrupt::TINT4),
             93 => Some(Interrupt::TINT5),
             94 => Some(Interrupt::TINT6),
             95 => Some(Interrupt::TINT7),
+            96 => Some(Interrupt::GPIOINT0A),
             98 => Some(Interrupt::GPIOINT1A),
             _ => None,
         }
@@ -1,30 +1,152 @@
 use core::{
     alloc::{GlobalAlloc, Layout},
+    arch::asm,
     convert::TryInto,
 };
 
 use crate::{
+    heap,
     internals::{
-        sysclock::millis,
-        tasks::{scheduler, TaskState},
+        barrier, mmu, mutex, semaphore,
+        sysclock::{self, millis},
+        tasks::{self, scheduler, Event, ExitReason, TaskKind, TaskState},
     },
     peripherals::{
+        adc,
         gpio::{self},
-        i2c,
+        i2c, pwm, spi, uart, watchdog,
     },
 };
-use shared::{i2c::I2cError, kernel::Syscall};
-use shared::{interrupts, kernel::SyscallReturnValue};
+use shared::{
+    collections::FixedMap,
+    interrupts::{self, CriticalSection},
+    kernel::SyscallReturnValue,
+};
+use shared::{
+    gpio::{GpioBank, GpioEdge, GpioMode, GpioPin},
+    i2c::I2cError,
+    kernel::{
+        MemInfo, PollTarget, Syscall, BARRIER_INVALID, JOIN_OWN_TASK, NO_PIN_CHANGE,
+        SEMAPHORE_INVALID,
+    },
+    pwm::PwmChannel,
+    sys,
+};
+
+pub struct SyscallError {}
+
+const BARRIER_FOLLOWER: u32 = 0;
+const BARRIER_LEADER: u32 = 1;
+
+const MAX_SLEEP_MICROS: u32 = 1000;
+
+// numbers below are reserved for the built-in syscalls
+const FIRST_CUSTOM_SYSCALL: u32 = 0x40;
+const MAX_CUSTOM_SYSCALLS: usize = 16;
+
+pub type SyscallHandler = fn(&TrapFrame) -> SyscallReturn;
+
+static CUSTOM_SYSCALLS: CriticalSection<FixedMap<u32, SyscallHandler, MAX_CUSTOM_SYSCALLS>> =
+    CriticalSection::new(FixedMap::new());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyscallRegistrationError {
+    // the number belongs to a built-in syscall
+    Reserved,
+    AlreadyRegistered,
+    NoFreeSlot,
+}
+
+// Installs a handler for a syscall number the kernel doesn't know, so new
+// syscalls can be tried out without touching the decoding in swi_handler
+pub fn register_syscall(
+    number: u32,
+    handler: SyscallHandler,
+) -> Result<(), SyscallRegistrationError> {
+    if number < FIRST_CUSTOM_SYSCALL {
+        return Err(SyscallRegistrationError::Reserved);
+    }
+
+    let mut syscalls = CUSTOM_SYSCALLS.lock();
+    if syscalls.contains_key(number) {
+        return Err(SyscallRegistrationError::AlreadyRegistered);
+    }
 
-struct SyscallError {}
+    syscalls
+        .insert(number, handler)
+        .map(|_| ())
+        .map_err(|_| SyscallRegistrationError::NoFreeSlot)
+}
+
+pub fn unregister_syscall(number: u32) -> Option<SyscallHandler> {
+    CUSTOM_SYSCALLS.lock().remove(number)
+}
+
+fn custom_syscall(number: u32) -> Option<SyscallHandler> {
+    CUSTOM_SYSCALLS.lock().get(number).copied()
+}
 
 #[repr(C)]
-struct TrapFrame {
-    r0: u32,
-    r1: u32,
-    r2: u32,
-    r3: u32,
-    r12: u32,
+pub struct TrapFrame {
+    pub r0: u32,
+    pub r1: u32,
+    pub r2: u32,
+    pub r3: u32,
+    // the syscall number
+    pub r12: u32,
+}
+
+impl TrapFrame {
+    fn gpio_pin(&self, bank: u32, pin: u32) -> Result<GpioPin, SyscallError> {
+        let bank = GpioBank::try_from(bank).map_err(|_| SyscallError {})?;
+        if pin >= 32 {
+            return Err(SyscallError {});
+        }
+
+        Ok((pin, bank))
+    }
+
+    // Buffer of the current task, which the kernel is going to read from
+    fn user_buffer<'a>(&self, address: u32, len: u32) -> Result<&'a [u8], SyscallError> {
+        if len == 0 {
+            return Ok(&[]);
+        }
+
+        match scheduler().current() {
+            Some(task) if task.owns(address, len) => {
+                Ok(unsafe { core::slice::from_raw_parts(address as *const u8, len as usize) })
+            }
+            _ => Err(SyscallError {}),
+        }
+    }
+
+    // Array of `len` values of the current task, which the kernel is going to
+    // write to
+    fn user_slice_mut<'a, T>(&self, address: u32, len: u32) -> Result<&'a mut [T], SyscallError> {
+        let size = len
+            .checked_mul(core::mem::size_of::<T>() as u32)
+            .ok_or(SyscallError {})?;
+        if !(address as usize).is_multiple_of(core::mem::align_of::<T>()) {
+            return Err(SyscallError {});
+        }
+
+        let buffer = self.user_buffer_mut(address, size)?;
+        Ok(unsafe { core::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut T, len as usize) })
+    }
+
+    // Buffer of the current task, which the kernel is going to write to
+    fn user_buffer_mut<'a>(&self, address: u32, len: u32) -> Result<&'a mut [u8], SyscallError> {
+        if len == 0 {
+            return Ok(&mut []);
+        }
+
+        match scheduler().current() {
+            Some(task) if task.owns_writable(address, len) => {
+                Ok(unsafe { core::slice::from_raw_parts_mut(address as *mut u8, len as usize) })
+            }
+            _ => Err(SyscallError {}),
+        }
+    }
 }
 
 impl<'a> TryInto<Syscall<'a>> for &TrapFrame {
@@ -32,7 +154,9 @@ impl<'a> TryInto<Syscall<'a>> for &TrapFrame {
 
     fn try_into(self) -> Result<Syscall<'a>, Self::Error> {
         match self.r12 {
-            0 => Ok(Syscall::Exit),
+            0 => Ok(Syscall::Exit {
+                code: self.r0 as i32,
+            }),
             1 => Ok(Syscall::Yield {
                 sp: self.r0,
                 pc: self.r1,
@@ -43,52 +167,193 @@ impl<'a> TryInto<Syscall<'a>> for &TrapFrame {
             }),
             2 => Ok(Syscall::Millis),
             3 => Ok(Syscall::GpioRead {
-                pin: (self.r1, self.r0.into()),
+                pin: self.gpio_pin(self.r0, self.r1)?,
             }),
             4 => Ok(Syscall::GpioWrite {
-                pin: (self.r1, self.r0.into()),
+                pin: self.gpio_pin(self.r0, self.r1)?,
                 value: self.r2 != 0,
             }),
             5 => Ok(Syscall::I2cWrite {
                 address: self.r0 as u8,
-                data: unsafe { core::slice::from_raw_parts(self.r1 as *mut u8, self.r2 as usize) },
+                data: self.user_buffer(self.r1, self.r2)?,
             }),
             6 => Ok(Syscall::Panic),
             7 => Ok(Syscall::Alloc {
-                layout: unsafe {
-                    Layout::from_size_align_unchecked(self.r0 as usize, self.r1 as usize)
-                },
+                layout: Layout::from_size_align(self.r0 as usize, self.r1 as usize)
+                    .map_err(|_| SyscallError {})?,
             }),
             8 => Ok(Syscall::Dealloc {
                 ptr: self.r0 as *mut u8,
-                layout: unsafe {
-                    Layout::from_size_align_unchecked(self.r1 as usize, self.r2 as usize)
+                layout: Layout::from_size_align(self.r1 as usize, self.r2 as usize)
+                    .map_err(|_| SyscallError {})?,
+            }),
+            9 => Ok(Syscall::GpioPulse {
+                pin: self.gpio_pin(self.r0, self.r1)?,
+                micros: self.r2,
+            }),
+            10 => Ok(Syscall::SleepCancelable {
+                sp: self.r0,
+                pc: self.r1,
+                until: self.r2,
+            }),
+            11 => Ok(Syscall::Wake {
+                id: self.r0 as usize,
+            }),
+            12 => Ok(Syscall::GpioCompareSwap {
+                pin: self.gpio_pin(self.r0, self.r1)?,
+                expected: self.r2 != 0,
+                new: self.r3 != 0,
+            }),
+            13 => Ok(Syscall::SetQuantum { millis: self.r0 }),
+            14 => Ok(Syscall::TaskHeapRemaining),
+            15 => Ok(Syscall::Poll {
+                what: match self.r0 {
+                    0 => PollTarget::I2cIdle,
+                    1 => PollTarget::GpioEdge {
+                        pin: self.gpio_pin(self.r1, self.r2)?,
+                    },
+                    _ => return Err(SyscallError {}),
                 },
             }),
+            16 => Ok(Syscall::DumpPageTable),
+            17 => Ok(Syscall::MemInfo {
+                info: &mut self.user_slice_mut::<MemInfo>(self.r0, 1)?[0],
+            }),
+            18 => Ok(Syscall::BarrierCreate { parties: self.r0 }),
+            19 => Ok(Syscall::BarrierWait {
+                sp: self.r0,
+                pc: self.r1,
+                id: self.r2 as usize,
+            }),
+            20 => Ok(Syscall::SleepMicros { micros: self.r0 }),
+            21 => Ok(Syscall::Reschedule {
+                sp: self.r0,
+                pc: self.r1,
+            }),
+            22 => Ok(Syscall::Sleep {
+                sp: self.r0,
+                pc: self.r1,
+                millis: self.r2,
+            }),
+            23 => Ok(Syscall::SchedulerStatus),
+            24 => Ok(Syscall::I2cRead {
+                address: self.r0 as u8,
+                buffer: self.user_buffer_mut(self.r1, self.r2)?,
+            }),
+            25 => Ok(Syscall::SetName {
+                name: self.user_buffer(self.r0, self.r1)?,
+            }),
+            26 => Ok(Syscall::GpioToggle {
+                pin: self.gpio_pin(self.r0, self.r1)?,
+            }),
+            27 => Ok(Syscall::GpioMode {
+                pin: self.gpio_pin(self.r0, self.r1)?,
+                mode: GpioMode::try_from(self.r2).map_err(|_| SyscallError {})?,
+            }),
+            28 => Ok(Syscall::I2cStart {
+                address: self.r0 as u8,
+                data: self.user_buffer(self.r1, self.r2)?,
+            }),
+            29 => Ok(Syscall::WaitI2cDone {
+                sp: self.r0,
+                pc: self.r1,
+                handle: self.r2,
+            }),
+            30 => Ok(Syscall::Time64 {
+                micros: &mut self.user_slice_mut::<u64>(self.r0, 1)?[0],
+            }),
+            31 => Ok(Syscall::Print {
+                data: self.user_buffer(self.r0, self.r1)?,
+            }),
+            32 => Ok(Syscall::Spawn {
+                code: self.user_buffer(self.r0, self.r1)?,
+            }),
+            33 => Ok(Syscall::TaskId),
+            34 => Ok(Syscall::PwmDuty {
+                channel: PwmChannel::try_from(self.r0).map_err(|_| SyscallError {})?,
+                duty: self.r1.min(100) as u8,
+            }),
+            35 if self.r0 < adc::CHANNELS as u32 => Ok(Syscall::AdcRead {
+                channel: self.r0 as u8,
+            }),
+            36 => Ok(Syscall::GpioWaitEdge {
+                sp: self.r0,
+                pc: self.r1,
+                pin: self.gpio_pin(self.r2, self.r3 & 0xFF)?,
+                edge: GpioEdge::try_from(self.r3 >> 8).map_err(|_| SyscallError {})?,
+            }),
+            37 => Ok(Syscall::SpiTransfer {
+                buffer: self.user_buffer_mut(self.r0, self.r1)?,
+            }),
+            38 => Ok(Syscall::WatchdogKick),
+            39 => Ok(Syscall::Join {
+                sp: self.r0,
+                pc: self.r1,
+                id: self.r2 as usize,
+            }),
+            40 => Ok(Syscall::I2cScan {
+                buffer: self.user_buffer_mut(self.r0, self.r1)?,
+            }),
+            41 => Ok(Syscall::MutexCreate),
+            42 => Ok(Syscall::MutexLock {
+                sp: self.r0,
+                pc: self.r1,
+                id: self.r2 as usize,
+            }),
+            43 => Ok(Syscall::MutexUnlock {
+                id: self.r0 as usize,
+            }),
+            44 => Ok(Syscall::SemaphoreCreate { count: self.r0 }),
+            45 => Ok(Syscall::SemaphoreWait {
+                sp: self.r0,
+                pc: self.r1,
+                id: self.r2 as usize,
+            }),
+            46 => Ok(Syscall::SemaphoreSignal {
+                id: self.r0 as usize,
+            }),
+            47 => Ok(Syscall::GpioNextChange),
+            48 => Ok(Syscall::Micros),
+            49 => Ok(Syscall::Kill {
+                id: self.r0 as usize,
+            }),
+            50 => Ok(Syscall::TaskInfo {
+                tasks: self.user_slice_mut(self.r0, self.r1)?,
+            }),
+            51 => Ok(Syscall::MutexDestroy {
+                id: self.r0 as usize,
+            }),
+            52 => Ok(Syscall::SemaphoreDestroy {
+                id: self.r0 as usize,
+            }),
+            53 => Ok(Syscall::BarrierDestroy {
+                id: self.r0 as usize,
+            }),
             _ => Err(SyscallError {}),
         }
     }
 }
 
 #[repr(C)]
-struct SyscallReturn {
+pub struct SyscallReturn {
     exit: bool,
     value: SyscallReturnValue,
 }
 
 impl SyscallReturn {
-    fn exit() -> Self {
+    // the current task was switched out, so nothing is returned to it
+    pub fn exit() -> Self {
         SyscallReturn {
             exit: true,
             value: SyscallReturnValue { none: () },
         }
     }
 
-    fn value(value: SyscallReturnValue) -> Self {
+    pub fn value(value: SyscallReturnValue) -> Self {
         SyscallReturn { exit: false, value }
     }
 
-    fn none() -> Self {
+    pub fn none() -> Self {
         SyscallReturn {
             exit: false,
             value: SyscallReturnValue { none: () },
@@ -96,23 +361,44 @@ impl SyscallReturn {
     }
 }
 
+fn mem_info() -> MemInfo {
+    let (used_pages, total_pages) = mmu::page_usage();
+
+    MemInfo {
+        total_pages: total_pages as u32,
+        used_pages: used_pages as u32,
+        kernel_heap_used: heap::used() as u32,
+        kernel_heap_free: heap::remaining() as u32,
+        tasks: scheduler().live_tasks() as u32,
+    }
+}
+
 #[no_mangle]
 extern "C" fn swi_handler(frame: &TrapFrame) -> SyscallReturn {
+    if let Some(task) = scheduler().current() {
+        if task.stack_overflowed(user_sp()) {
+            // interrupts are off, so the interrupt driven I2C can't be used
+            uart::println(format_args!("stack overflow in task {}", task.id()));
+            return terminate_current(ExitReason::StackOverflow);
+        }
+    }
+
+    if let Some(handler) = custom_syscall(frame.r12) {
+        return handler(frame);
+    }
+
     let syscall: Syscall = match frame.try_into() {
         Ok(syscall) => syscall,
-        Err(_) => panic!("invalid syscall"),
+        Err(_) => return terminate_current(ExitReason::InvalidSyscall),
     };
 
     match syscall {
-        Syscall::Exit => {
-            let scheduler = scheduler();
-            if let Some(task) = scheduler.current() {
-                task.terminate();
+        Syscall::Exit { code } => {
+            if let Some(task) = scheduler().current() {
+                task.exit_code = Some(code);
             }
 
-            scheduler.cycle();
-
-            SyscallReturn::exit()
+            terminate_current(ExitReason::Exit)
         }
         Syscall::Yield {
             sp,
@@ -147,6 +433,9 @@ extern "C" fn swi_handler(frame: &TrapFrame) -> SyscallReturn {
             SyscallReturn::exit()
         }
         Syscall::Millis => SyscallReturn::value(SyscallReturnValue { millis: millis() }),
+        Syscall::Micros => SyscallReturn::value(SyscallReturnValue {
+            micros: sysclock::micros(),
+        }),
         Syscall::GpioWrite { pin, value } => {
             gpio::write(pin, value);
 
@@ -158,7 +447,7 @@ extern "C" fn swi_handler(frame: &TrapFrame) -> SyscallReturn {
             SyscallReturn::value(SyscallReturnValue { gpio_read: value })
         }
         Syscall::I2cWrite { address, data } => {
-            let i2c = i2c::get_i2c();
+            let i2c = i2c::get_i2c(i2c::DEFAULT_MODULE);
             let mut error: I2cError = I2cError::Success;
             interrupts::enabled(|| {
                 if let Err(err) = i2c.write(address, data) {
@@ -168,41 +457,501 @@ extern "C" fn swi_handler(frame: &TrapFrame) -> SyscallReturn {
 
             SyscallReturn::value(SyscallReturnValue { i2c_write: error })
         }
-        Syscall::Panic => {
+        Syscall::Panic => terminate_current(ExitReason::Panic),
+        Syscall::Alloc { layout } => {
+            let scheduler = scheduler();
+            if let Some(task) = scheduler.current() {
+                let ptr = task.alloc(layout);
+                return SyscallReturn::value(SyscallReturnValue { alloc: ptr });
+            }
+
+            SyscallReturn::none()
+        }
+        Syscall::Dealloc { ptr, layout } => {
             let scheduler = scheduler();
+            if let Some(task) = scheduler.current() {
+                unsafe { task.allocator.dealloc(ptr, layout) };
+            }
 
+            SyscallReturn::none()
+        }
+        Syscall::GpioPulse { pin, micros } => {
+            gpio::pulse(pin, micros);
+
+            SyscallReturn::none()
+        }
+        Syscall::SleepCancelable { sp, pc, until } => {
+            let scheduler = scheduler();
             if let Some(task) = scheduler.current() {
-                task.terminate();
+                task.context.pc = pc;
+                task.context.sp = sp;
+                task.state = TaskState::Sleeping { until };
+                task.resume_value = Some(false as u32);
             }
 
             scheduler.cycle();
 
             SyscallReturn::exit()
         }
-        Syscall::Alloc { layout } => {
+        Syscall::Wake { id } => {
+            let woken = scheduler().wake(id);
+
+            SyscallReturn::value(SyscallReturnValue { woken })
+        }
+        Syscall::GpioCompareSwap { pin, expected, new } => {
+            let swapped = gpio::compare_swap(pin, expected, new);
+
+            SyscallReturn::value(SyscallReturnValue {
+                gpio_swapped: swapped,
+            })
+        }
+        Syscall::SetQuantum { millis } => {
+            if let Some(task) = scheduler().current() {
+                task.set_quantum(millis);
+            }
+
+            SyscallReturn::none()
+        }
+        Syscall::TaskHeapRemaining => {
+            let remaining = scheduler()
+                .current()
+                .map(|task| task.allocator.remaining())
+                .unwrap_or(0);
+
+            SyscallReturn::value(SyscallReturnValue {
+                heap_remaining: remaining,
+            })
+        }
+        Syscall::Poll { what } => {
+            let ready = match what {
+                PollTarget::I2cIdle => i2c::get_i2c(i2c::DEFAULT_MODULE).idle(),
+                PollTarget::GpioEdge { pin } => gpio::edge_pending(pin),
+            };
+
+            SyscallReturn::value(SyscallReturnValue { ready })
+        }
+        Syscall::DumpPageTable => {
+            // only available in debug builds, as it leaks the physical layout
+            if cfg!(debug_assertions) {
+                if let Some(task) = scheduler().current() {
+                    interrupts::enabled(|| task.dump_pages());
+                }
+            }
+
+            SyscallReturn::none()
+        }
+        Syscall::MemInfo { info } => {
+            *info = mem_info();
+
+            SyscallReturn::none()
+        }
+        Syscall::BarrierCreate { parties } => {
+            let barrier = scheduler()
+                .current_task_id()
+                .and_then(|current| barrier::create(parties, current))
+                .map(|id| id as u32)
+                .unwrap_or(u32::MAX);
+
+            SyscallReturn::value(SyscallReturnValue { barrier })
+        }
+        Syscall::BarrierWait { sp, pc, id } => match scheduler()
+            .current_task_id()
+            .and_then(|current| barrier::arrive(id, current))
+        {
+            Some(false) => {
+                let scheduler = scheduler();
+                if let Some(task) = scheduler.current() {
+                    task.context.pc = pc;
+                    task.context.sp = sp;
+                    task.state = TaskState::Blocked(Event::Barrier(id));
+                    task.resume_value = Some(BARRIER_FOLLOWER);
+                }
+
+                scheduler.cycle();
+
+                SyscallReturn::exit()
+            }
+            // the last task to arrive releases the others and continues
+            Some(true) => {
+                scheduler().wake_all(Event::Barrier(id), BARRIER_FOLLOWER);
+
+                SyscallReturn::value(SyscallReturnValue {
+                    barrier: BARRIER_LEADER,
+                })
+            }
+            None => SyscallReturn::value(SyscallReturnValue {
+                barrier: BARRIER_INVALID,
+            }),
+        },
+        Syscall::BarrierDestroy { id } => {
+            let destroyed = scheduler()
+                .current_task_id()
+                .is_some_and(|current| barrier::destroy(id, current));
+
+            SyscallReturn::value(SyscallReturnValue { destroyed })
+        }
+        Syscall::SleepMicros { micros } => {
+            // busy waits, so longer delays have to be slept in milliseconds
+            sysclock::delay_us(micros.min(MAX_SLEEP_MICROS));
+
+            SyscallReturn::none()
+        }
+        Syscall::Reschedule { sp, pc } => {
             let scheduler = scheduler();
+            if !scheduler.runnable_peer() {
+                return SyscallReturn::value(SyscallReturnValue { rescheduled: false });
+            }
+
             if let Some(task) = scheduler.current() {
-                let ptr = unsafe { task.allocator.alloc(layout) };
-                return SyscallReturn::value(SyscallReturnValue { alloc: ptr });
+                task.context.pc = pc;
+                task.context.sp = sp;
+                task.state = TaskState::Stored;
+                task.resume_value = Some(true as u32);
+            }
+
+            scheduler.cycle();
+
+            SyscallReturn::exit()
+        }
+        Syscall::Sleep {
+            sp,
+            pc,
+            millis: duration,
+        } => {
+            let scheduler = scheduler();
+            if let Some(task) = scheduler.current() {
+                task.context.pc = pc;
+                task.context.sp = sp;
+                task.state = TaskState::Waiting {
+                    until: millis().saturating_add(duration),
+                };
+            }
+
+            scheduler.cycle();
+
+            SyscallReturn::exit()
+        }
+        Syscall::SchedulerStatus => SyscallReturn::value(SyscallReturnValue {
+            scheduler_status: scheduler().status,
+        }),
+        Syscall::I2cRead { address, buffer } => {
+            let i2c = i2c::get_i2c(i2c::DEFAULT_MODULE);
+            let mut error: I2cError = I2cError::Success;
+            interrupts::enabled(|| {
+                if let Err(err) = i2c.read(address, buffer) {
+                    error = err
+                }
+            });
+
+            SyscallReturn::value(SyscallReturnValue { i2c_read: error })
+        }
+        Syscall::SetName { name } => {
+            if let Some(task) = scheduler().current() {
+                task.set_name(name);
             }
 
             SyscallReturn::none()
         }
-        Syscall::Dealloc { ptr, layout } => {
+        Syscall::I2cStart { address, data } => {
+            let handle = i2c::get_i2c(i2c::DEFAULT_MODULE)
+                .start_write(address, data)
+                .unwrap_or(0);
+
+            SyscallReturn::value(SyscallReturnValue { i2c_handle: handle })
+        }
+        Syscall::WaitI2cDone { sp, pc, handle } => {
+            let i2c = i2c::get_i2c(i2c::DEFAULT_MODULE);
+            if !i2c.in_flight(handle) {
+                let result = i2c.result(handle).unwrap_or(I2cError::UnknownTransfer);
+                return SyscallReturn::value(SyscallReturnValue { i2c_write: result });
+            }
+
             let scheduler = scheduler();
             if let Some(task) = scheduler.current() {
-                unsafe { task.allocator.dealloc(ptr, layout) };
+                task.context.pc = pc;
+                task.context.sp = sp;
+                task.state = TaskState::Blocked(Event::I2cDone(handle));
+            }
+
+            scheduler.cycle();
+
+            SyscallReturn::exit()
+        }
+        Syscall::Time64 { micros } => {
+            *micros = sysclock::micros64();
+
+            SyscallReturn::none()
+        }
+        Syscall::Print { data } => {
+            uart::get_uart().write(data);
+
+            SyscallReturn::none()
+        }
+        Syscall::Spawn { code } => {
+            // the code is copied into the pages of the new task right away.
+            // The slice is kept only to respawn services, which a spawned
+            // task never is, as it points into the pages of the caller.
+            let code = unsafe { core::slice::from_raw_parts(code.as_ptr(), code.len()) };
+            let id = scheduler()
+                .create_task(b"spawned", code, TaskKind::Oneshot, tasks::DEFAULT_PRIORITY)
+                .map(|id| id as u32)
+                .unwrap_or(u32::MAX);
+
+            SyscallReturn::value(SyscallReturnValue { spawned: id })
+        }
+        Syscall::TaskId => {
+            let id = scheduler()
+                .current_task_id()
+                .map_or(u32::MAX, |id| id as u32);
+
+            SyscallReturn::value(SyscallReturnValue { task_id: id })
+        }
+        Syscall::PwmDuty { channel, duty } => {
+            pwm::init(channel);
+            pwm::set_duty(channel, duty);
+
+            SyscallReturn::none()
+        }
+        Syscall::GpioWaitEdge { sp, pc, pin, edge } => {
+            gpio::enable_edge(pin, edge);
+
+            let scheduler = scheduler();
+            if let Some(task) = scheduler.current() {
+                task.context.pc = pc;
+                task.context.sp = sp;
+                task.state = TaskState::Blocked(Event::GpioEdge {
+                    bank: pin.1.index(),
+                    pin: pin.0,
+                });
+            }
+
+            scheduler.cycle();
+
+            SyscallReturn::exit()
+        }
+        Syscall::SpiTransfer { buffer } => {
+            let spi = spi::get_spi();
+            spi.init_default();
+            spi.exchange(buffer);
+
+            SyscallReturn::none()
+        }
+        Syscall::WatchdogKick => {
+            // kicking a stopped watchdog would only waste the wait for the
+            // write to be synchronized
+            if watchdog::enabled() {
+                watchdog::kick();
+            }
+
+            SyscallReturn::none()
+        }
+        Syscall::Join { sp, pc, id } => {
+            let scheduler = scheduler();
+            // the task would wait for itself forever
+            if scheduler.current_task_id() == Some(id) {
+                return SyscallReturn::value(SyscallReturnValue {
+                    joined: JOIN_OWN_TASK,
+                });
+            }
+
+            // a terminated task keeps its exit code until the slot is reused.
+            // The code is returned in r1, which is only restored from the
+            // saved context, so the task is resumed like after a yield.
+            let (status, code) = scheduler.join_result(id);
+            let alive = scheduler.alive(id);
+            if let Some(task) = scheduler.current() {
+                task.context.pc = pc;
+                task.context.sp = sp;
+                if alive {
+                    task.state = TaskState::Blocked(Event::TaskExit(id));
+                } else {
+                    task.state = TaskState::Stored;
+                    task.resume_value = Some(status);
+                    task.resume_extra = Some(code);
+                }
+            }
+
+            scheduler.cycle();
+
+            SyscallReturn::exit()
+        }
+        Syscall::Kill { id } => {
+            let scheduler = scheduler();
+            if scheduler.current_task_id() == Some(id) {
+                return terminate_current(ExitReason::Exit);
+            }
+
+            if !scheduler.alive(id) {
+                return SyscallReturn::value(SyscallReturnValue { killed: false });
+            }
+
+            // joining tasks are woken by the termination
+            scheduler.terminate(id, ExitReason::Killed);
+
+            SyscallReturn::value(SyscallReturnValue { killed: true })
+        }
+        Syscall::TaskInfo { tasks } => SyscallReturn::value(SyscallReturnValue {
+            tasks: scheduler().task_info(tasks) as u32,
+        }),
+        Syscall::I2cScan { buffer } => {
+            let i2c = i2c::get_i2c(i2c::DEFAULT_MODULE);
+            let found = interrupts::enabled(|| i2c.scan());
+
+            let len = found.len().min(buffer.len());
+            buffer[..len].copy_from_slice(&found[..len]);
+
+            SyscallReturn::value(SyscallReturnValue {
+                i2c_found: len as u32,
+            })
+        }
+        Syscall::MutexCreate => {
+            let mutex = scheduler()
+                .current_task_id()
+                .and_then(mutex::create)
+                .map(|id| id as u32)
+                .unwrap_or(u32::MAX);
+
+            SyscallReturn::value(SyscallReturnValue { mutex })
+        }
+        Syscall::MutexLock { sp, pc, id } => {
+            let scheduler = scheduler();
+            let Some(current) = scheduler.current_task_id() else {
+                return SyscallReturn::none();
+            };
+
+            match mutex::try_lock(id, current) {
+                Ok(true) => SyscallReturn::value(SyscallReturnValue {
+                    locked: mutex::MUTEX_LOCKED,
+                }),
+                // the task is resumed once the mutex was handed to it, or
+                // with MUTEX_INVALID once it was destroyed
+                Ok(false) => {
+                    if let Some(task) = scheduler.current() {
+                        task.context.pc = pc;
+                        task.context.sp = sp;
+                        task.state = TaskState::Blocked(Event::Mutex(id));
+                    }
+
+                    scheduler.cycle();
+
+                    SyscallReturn::exit()
+                }
+                Err(error) => SyscallReturn::value(SyscallReturnValue {
+                    locked: error.into(),
+                }),
+            }
+        }
+        Syscall::MutexUnlock { id } => {
+            let unlocked = scheduler()
+                .current_task_id()
+                .is_some_and(|current| mutex::unlock(id, current));
+
+            SyscallReturn::value(SyscallReturnValue { unlocked })
+        }
+        Syscall::MutexDestroy { id } => {
+            let destroyed = scheduler()
+                .current_task_id()
+                .is_some_and(|current| mutex::destroy(id, current));
+
+            SyscallReturn::value(SyscallReturnValue { destroyed })
+        }
+        Syscall::SemaphoreCreate { count } => {
+            let semaphore = scheduler()
+                .current_task_id()
+                .and_then(|current| semaphore::create(count, current))
+                .map(|id| id as u32)
+                .unwrap_or(u32::MAX);
+
+            SyscallReturn::value(SyscallReturnValue { semaphore })
+        }
+        Syscall::SemaphoreWait { sp, pc, id } => match semaphore::try_wait(id) {
+            Some(true) => SyscallReturn::value(SyscallReturnValue {
+                semaphore: semaphore::SEMAPHORE_ACQUIRED,
+            }),
+            // the task is resumed once a signal passed a unit to it, or with
+            // SEMAPHORE_INVALID once it was destroyed
+            Some(false) => {
+                let scheduler = scheduler();
+                if let Some(task) = scheduler.current() {
+                    task.context.pc = pc;
+                    task.context.sp = sp;
+                    task.state = TaskState::Blocked(Event::Semaphore(id));
+                }
+
+                scheduler.cycle();
+
+                SyscallReturn::exit()
             }
+            None => SyscallReturn::value(SyscallReturnValue {
+                semaphore: SEMAPHORE_INVALID,
+            }),
+        },
+        Syscall::SemaphoreSignal { id } => SyscallReturn::value(SyscallReturnValue {
+            signaled: semaphore::signal(id),
+        }),
+        Syscall::SemaphoreDestroy { id } => {
+            let destroyed = scheduler()
+                .current_task_id()
+                .is_some_and(|current| semaphore::destroy(id, current));
+
+            SyscallReturn::value(SyscallReturnValue { destroyed })
+        }
+        Syscall::GpioNextChange => {
+            let change = gpio::next_pin_change().map_or(NO_PIN_CHANGE, |change| {
+                let (pin, bank) = change.pin;
+                (bank.index() as u32) << 8 | pin | (change.level as u32) << 16
+            });
+
+            SyscallReturn::value(SyscallReturnValue { pin_change: change })
+        }
+        Syscall::AdcRead { channel } => SyscallReturn::value(SyscallReturnValue {
+            adc: adc::read(channel).unwrap_or(0),
+        }),
+        Syscall::GpioMode { pin, mode } => {
+            gpio::pin_mode(pin, mode);
 
             SyscallReturn::none()
         }
+        Syscall::GpioToggle { pin } => {
+            let value = gpio::toggle(pin);
+
+            SyscallReturn::value(SyscallReturnValue {
+                gpio_toggled: value,
+            })
+        }
     }
 }
 
+// Stack pointer of the interrupted task, user and system mode share it
+fn user_sp() -> u32 {
+    let sp: u32;
+
+    unsafe {
+        asm!("cps #0x1F", "mov {sp}, sp", "cps #0x13", sp = out(reg) sp);
+    }
+
+    sp
+}
+
+fn terminate_current(reason: ExitReason) -> SyscallReturn {
+    let scheduler = scheduler();
+    if let Some(id) = scheduler.current_task_id() {
+        scheduler.terminate(id, reason);
+    }
+
+    scheduler.cycle();
+
+    SyscallReturn::exit()
+}
+
 #[no_mangle]
 pub fn kernel_loop() {
     loop {
         let scheduler = scheduler();
         scheduler.switch();
+
+        // switch only returns if no task can run, which changes at the
+        // earliest with the next interrupt
+        sys::wfi();
     }
 }
@@ -5,10 +5,10 @@ use include_programs::include_programs;
 use internals::{
     mmu,
     sysclock::{self},
-    tasks::{self, create_task},
+    tasks::{self, create_task, TaskKind},
 };
 use kernel::kernel_loop;
-use peripherals::{gpio, i2c};
+use peripherals::{adc, gpio, i2c, uart};
 use shared::gpio::pins::GPIO1_24;
 
 pub mod exceptions;
@@ -16,10 +16,12 @@ pub mod heap;
 pub mod internals;
 pub mod interrupts;
 pub mod kernel;
+pub mod output;
 pub mod peripherals;
 pub mod pinmux;
 
-static PROGRAMS: &[&[u8]] = include_programs!();
+// file name, contents and whether the program is a service, for every program
+static PROGRAMS: &[(&str, &[u8], bool)] = include_programs!();
 
 #[no_mangle]
 pub fn _start() {
@@ -31,15 +33,25 @@ pub fn _start() {
     mmu::initialize();
     heap::initialize();
     pinmux::configure();
+    uart::initialize();
     gpio::initialize();
     i2c::initialize();
+    adc::initialize();
     sysclock::initialize();
     tasks::init();
+    tasks::set_exit_hook(tasks::restart_services);
 
     gpio::write(GPIO1_24, true);
 
-    for program in PROGRAMS {
-        create_task(program);
+    for (name, program, service) in PROGRAMS {
+        let kind = if *service {
+            TaskKind::Service
+        } else {
+            TaskKind::Oneshot
+        };
+
+        // a program which can't be loaded is skipped
+        let _ = create_task(name.as_bytes(), program, kind, tasks::DEFAULT_PRIORITY);
     }
 
     kernel_loop();
@@ -0,0 +1,156 @@
+use core::{
+    fmt::{self, Arguments, Write},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use shared::{collections::ArrayVec, interrupts, sync::RingBuffer};
+
+use crate::peripherals::{i2c, uart};
+
+const BUFFER_SIZE: usize = 128;
+const RING_SIZE: usize = 1024;
+
+pub trait OutputSink {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result;
+}
+
+#[derive(Clone, Copy)]
+pub enum Sink {
+    I2c,
+    Uart,
+    Ring,
+}
+
+impl Sink {
+    fn get(self) -> &'static mut dyn OutputSink {
+        match self {
+            Sink::I2c => i2c::get_i2c(i2c::DEFAULT_MODULE),
+            Sink::Uart => uart::get_uart(),
+            Sink::Ring => {
+                let sink = &raw mut RING_SINK;
+                unsafe { &mut *sink }
+            }
+        }
+    }
+}
+
+static mut SINK: Sink = Sink::I2c;
+
+// prints which couldn't be written completely, e.g. because the device on
+// the bus didn't acknowledge or the ring was full
+static FAILED_WRITES: AtomicU32 = AtomicU32::new(0);
+
+pub fn select(sink: Sink) {
+    unsafe { SINK = sink };
+}
+
+pub fn failed_writes() -> u32 {
+    FAILED_WRITES.load(Ordering::Relaxed)
+}
+
+// Keeps the output in memory until it's read with `read_ring`. Both sides mask
+// interrupts, so there's only ever one producer and one consumer.
+struct RingSink;
+
+static RING: RingBuffer<u8, RING_SIZE> = RingBuffer::new();
+static mut RING_SINK: RingSink = RingSink;
+
+impl OutputSink for RingSink {
+    // bytes which don't fit anymore are dropped
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        interrupts::free(|| {
+            for byte in data {
+                unsafe { RING.push(*byte) }.map_err(|_| fmt::Error)?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+// Moves the oldest output of the ring sink into `buffer`, returns how many
+// bytes were read
+pub fn read_ring(buffer: &mut [u8]) -> usize {
+    interrupts::free(|| {
+        let mut len = 0;
+        for slot in buffer.iter_mut() {
+            let Some(byte) = (unsafe { RING.pop() }) else {
+                break;
+            };
+
+            *slot = byte;
+            len += 1;
+        }
+
+        len
+    })
+}
+
+// Collects the formatted output, so a whole line is written to the sink at
+// once instead of one write per formatted piece
+struct Writer {
+    sink: &'static mut dyn OutputSink,
+    buffer: ArrayVec<u8, BUFFER_SIZE>,
+}
+
+impl Writer {
+    fn flush(&mut self) -> fmt::Result {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let result = self.sink.write_bytes(&self.buffer);
+        self.buffer.clear();
+        result
+    }
+}
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if self.buffer.is_full() {
+                self.flush()?;
+            }
+
+            let _ = self.buffer.push(byte);
+        }
+
+        Ok(())
+    }
+}
+
+fn writer() -> Writer {
+    Writer {
+        sink: unsafe { SINK }.get(),
+        buffer: ArrayVec::new(),
+    }
+}
+
+fn write(args: Arguments<'_>) {
+    let mut writer = writer();
+    if writer.write_fmt(args).and_then(|_| writer.flush()).is_err() {
+        FAILED_WRITES.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn print(args: Arguments<'_>) {
+    write(args);
+}
+
+pub fn println(args: Arguments<'_>) {
+    write(format_args!("{}\n", args));
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!($($arg)*))
+    }
+}
+
+#[macro_export]
+macro_rules! println {
+    ($($arg:tt)*) => {
+        $crate::output::println(format_args!($($arg)*))
+    }
+}
@@ -0,0 +1,60 @@
+use crate::internals::clock::{self, FuncClock};
+use shared::sys::{read_addr, write_addr};
+
+const ADC_TSC: u32 = 0x44E0_D000;
+
+const ADC_CTRL: u32 = 0x40;
+const ADC_CLKDIV: u32 = 0x4C;
+const ADC_STEPENABLE: u32 = 0x54;
+const ADC_STEPCONFIG1: u32 = 0x64;
+const ADC_STEPDELAY1: u32 = 0x68;
+const ADC_FIFO0COUNT: u32 = 0xE4;
+const ADC_FIFO0DATA: u32 = 0x100;
+
+pub const CHANNELS: u8 = 8;
+
+// the module is enabled, tags the samples with their step and allows
+// writing the step configuration
+const CTRL_ENABLE: u32 = 0b111;
+// divides the 24 MHz clock down to the 3 MHz the converter runs at
+const CLOCK_DIVIDER: u32 = 7;
+// 16 samples are averaged per conversion
+const STEP_AVERAGE_16: u32 = 0b100 << 2;
+const STEP_CHANNEL_SHIFT: u32 = 19;
+// step 1, step 0 is reserved for the touchscreen charge step
+const STEP_1: u32 = 1 << 1;
+
+const SAMPLE_MASK: u32 = 0xFFF;
+
+pub fn initialize() {
+    clock::enable(FuncClock::AdcTsc);
+
+    write_addr(ADC_TSC + ADC_CTRL, 0);
+    write_addr(ADC_TSC + ADC_CLKDIV, CLOCK_DIVIDER);
+    write_addr(ADC_TSC + ADC_STEPDELAY1, 0);
+    write_addr(ADC_TSC + ADC_CTRL, CTRL_ENABLE);
+
+    // drop samples left over from before a reset
+    while read_addr(ADC_TSC + ADC_FIFO0COUNT) > 0 {
+        read_addr(ADC_TSC + ADC_FIFO0DATA);
+    }
+}
+
+// Runs a single conversion of the channel and waits for its 12 bit sample.
+// Returns None for channels above 7.
+pub fn read(channel: u8) -> Option<u16> {
+    if channel >= CHANNELS {
+        return None;
+    }
+
+    write_addr(
+        ADC_TSC + ADC_STEPCONFIG1,
+        STEP_AVERAGE_16 | ((channel as u32) << STEP_CHANNEL_SHIFT),
+    );
+    // a one shot step disables itself once the sample is in the fifo
+    write_addr(ADC_TSC + ADC_STEPENABLE, STEP_1);
+
+    while read_addr(ADC_TSC + ADC_FIFO0COUNT) == 0 {}
+
+    Some((read_addr(ADC_TSC + ADC_FIFO0DATA) & SAMPLE_MASK) as u16)
+}
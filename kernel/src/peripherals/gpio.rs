@@ -1,17 +1,25 @@
+use core::convert::Infallible;
+
 use crate::{
-    internals::clock,
+    internals::{
+        clock, sysclock,
+        tasks::{scheduler, Event},
+    },
     interrupts::{self, Interrupt, Mode},
 };
+use embedded_hal::digital;
+pub use shared::gpio::{GpioEdge, GpioMode};
 use shared::{
     gpio::{GpioBank, GpioPin},
-    sys::{clear_bit, noop, read_addr, read_bit, set_bit, write_addr},
+    sync::RingBuffer,
+    sys::{clear_bit, read_addr, read_bit, set_bit, write_addr},
 };
 
-const GPIO1: u32 = 0x4804C000;
-
 const GPIO_OE: u32 = 0x134;
 const GPIO_DATAIN: u32 = 0x138;
 const GPIO_DATAOUT: u32 = 0x13C;
+const GPIO_CLEARDATAOUT: u32 = 0x190;
+const GPIO_SETDATAOUT: u32 = 0x194;
 
 const GPIO_IRQSTATUS_RAW_0: u32 = 0x24;
 const GPIO_IRQSTATUS_0: u32 = 0x2C;
@@ -20,10 +28,12 @@ const GPIO_RISINGDETECT: u32 = 0x148;
 const GPIO_FALLINGDETECT: u32 = 0x14C;
 
 pub fn initialize() {
-    clock::enable(clock::FuncClock::Gpio1);
+    // every bank is clocked, so any pin can be used by the syscalls
+    for bank in GpioBank::ALL {
+        clock::enable(bank_clock(bank));
+    }
 
-    interrupts::enable_interrupt(Interrupt::GPIOINT1A, Mode::IRQ, 1);
-    interrupts::register_handler(handle_interrupts, Interrupt::GPIOINT1A);
+    interrupts::enable_interrupt(Interrupt::GPIOINT1A, Mode::IRQ, 1, handle_interrupts);
 
     for i in 21..=24 {
         pin_mode((i, GpioBank::Gpio1), GpioMode::Output);
@@ -53,44 +63,242 @@ pub fn read((pin, bank): GpioPin) -> bool {
     read_bit(bank as u32 + GPIO_DATAIN, pin)
 }
 
-pub enum GpioMode {
-    Input,
-    Output,
+// Drives every pin of the bank contained in `mask` to its bit in `value`
+pub fn write_mask(bank: GpioBank, mask: u32, value: u32) {
+    write_addr(bank as u32 + GPIO_SETDATAOUT, mask & value);
+    write_addr(bank as u32 + GPIO_CLEARDATAOUT, mask & !value);
 }
 
-static mut GPIO_INTERRUPT_HANDLERS: [fn(); 32] = [noop; 32];
+// Sets the output to `new` only if it is currently driven to `expected`.
+// Interrupts are disabled, so no handler can change the pin in between.
+pub fn compare_swap((pin, bank): GpioPin, expected: bool, new: bool) -> bool {
+    shared::interrupts::free(|| {
+        if read_bit(bank as u32 + GPIO_DATAOUT, pin) != expected {
+            return false;
+        }
 
-// TODO, don't use just GPIO1 but make it dynamic
-fn handle_interrupts() {
-    let irq_raw = read_addr(GPIO1 + GPIO_IRQSTATUS_RAW_0);
-    let number = irq_raw.trailing_zeros();
+        write((pin, bank), new);
+        true
+    })
+}
 
-    unsafe {
-        GPIO_INTERRUPT_HANDLERS[number as usize]();
+// Inverts the output of the pin, returns the new level
+pub fn toggle((pin, bank): GpioPin) -> bool {
+    shared::interrupts::free(|| {
+        let value = !read_bit(bank as u32 + GPIO_DATAOUT, pin);
+        write((pin, bank), value);
+        value
+    })
+}
+
+// Drives the pin high for `micros` and low again, busy-waiting in between so
+// the pulse isn't stretched by a context switch
+pub fn pulse(pin: GpioPin, micros: u32) {
+    write(pin, true);
+    sysclock::delay_us(micros);
+    write(pin, false);
+}
+
+// Single pin for embedded_hal drivers, e.g. the reset or chip select line
+// of a sensor. The mode is left to the caller, see `output` and `input`.
+#[derive(Clone, Copy)]
+pub struct Pin {
+    pin: GpioPin,
+}
+
+impl Pin {
+    pub fn new(pin: GpioPin) -> Self {
+        Pin { pin }
+    }
+
+    pub fn output(pin: GpioPin) -> Self {
+        pin_mode(pin, GpioMode::Output);
+        Pin { pin }
     }
 
-    write_addr(GPIO1 + GPIO_IRQSTATUS_0, 1 << number);
+    pub fn input(pin: GpioPin) -> Self {
+        pin_mode(pin, GpioMode::Input);
+        Pin { pin }
+    }
 }
 
-pub fn register_interrupt(pin: u32, interrupt: GpioInterrupt, handler: fn()) {
-    unsafe {
-        GPIO_INTERRUPT_HANDLERS[pin as usize] = handler;
+impl digital::ErrorType for Pin {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for Pin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        write(self.pin, false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        write(self.pin, true);
+        Ok(())
+    }
+}
+
+impl digital::InputPin for Pin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(read(self.pin))
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!read(self.pin))
+    }
+}
+
+// Output pins driven together, e.g. the segments of a multiplexed display
+pub struct PinGroup<const N: usize> {
+    pins: [GpioPin; N],
+}
+
+impl<const N: usize> PinGroup<N> {
+    pub fn new(pins: [GpioPin; N]) -> Self {
+        assert!(N <= 32, "a pin group holds at most 32 pins");
+
+        for pin in pins {
+            pin_mode(pin, GpioMode::Output);
+        }
+
+        PinGroup { pins }
+    }
+
+    // Drives the n-th pin of the group to the n-th bit of `value`
+    pub fn write_bits(&self, value: u32) {
+        let mut masks = [0; GpioBank::ALL.len()];
+        let mut values = [0; GpioBank::ALL.len()];
+
+        for (bit, (pin, bank)) in self.pins.iter().enumerate() {
+            masks[bank.index()] |= 1 << pin;
+            if value & (1 << bit) != 0 {
+                values[bank.index()] |= 1 << pin;
+            }
+        }
+
+        for bank in GpioBank::ALL {
+            let index = bank.index();
+            if masks[index] != 0 {
+                write_mask(bank, masks[index], values[index]);
+            }
+        }
     }
+}
+
+const PINS_PER_BANK: usize = 32;
+
+// None for every pin without a handler, so an unexpected interrupt is just
+// acknowledged instead of being silently routed somewhere
+type BankHandlers = [Option<fn()>; PINS_PER_BANK];
+
+static mut GPIO_INTERRUPT_HANDLERS: [BankHandlers; GpioBank::ALL.len()] =
+    [[None; PINS_PER_BANK]; GpioBank::ALL.len()];
+
+const MAX_PIN_CHANGES: usize = 32;
+
+// An edge seen by the interrupt handler and the level right after it
+#[derive(Clone, Copy)]
+pub struct PinChange {
+    pub pin: GpioPin,
+    pub level: bool,
+}
 
-    set_bit(GPIO1 + GPIO_IRQSTATUS_SET0, pin);
+// filled by the interrupt handler, changes are dropped while it's full. Only
+// the handler pushes and only the syscall pops.
+static PIN_CHANGES: RingBuffer<PinChange, MAX_PIN_CHANGES> = RingBuffer::new();
 
-    match interrupt {
-        GpioInterrupt::Rising => set_bit(GPIO1 + GPIO_RISINGDETECT, pin),
-        GpioInterrupt::Falling => set_bit(GPIO1 + GPIO_FALLINGDETECT, pin),
-        GpioInterrupt::Change => {
-            set_bit(GPIO1 + GPIO_RISINGDETECT, pin);
-            set_bit(GPIO1 + GPIO_FALLINGDETECT, pin)
+// Oldest pin change which wasn't taken yet
+pub fn next_pin_change() -> Option<PinChange> {
+    unsafe { PIN_CHANGES.pop() }
+}
+
+// Services every pending pin of every bank, each bank is acknowledged on its
+// own
+fn handle_interrupts() {
+    for bank in GpioBank::ALL {
+        let mut pending = read_addr(bank as u32 + GPIO_IRQSTATUS_0);
+
+        while pending != 0 {
+            let number = pending.trailing_zeros();
+
+            let handler = unsafe { GPIO_INTERRUPT_HANDLERS[bank.index()][number as usize] };
+            if let Some(handler) = handler {
+                handler();
+            }
+
+            let pin = (number, bank);
+            let _ = unsafe {
+                PIN_CHANGES.push(PinChange {
+                    pin,
+                    level: read(pin),
+                })
+            };
+
+            let event = Event::GpioEdge {
+                bank: bank.index(),
+                pin: number,
+            };
+            scheduler().wake_all(event, 0);
+
+            write_addr(bank as u32 + GPIO_IRQSTATUS_0, 1 << number);
+            pending &= !(1 << number);
         }
     }
 }
 
-pub enum GpioInterrupt {
-    Rising,
-    Falling,
-    Change,
+fn bank_interrupt(bank: GpioBank) -> Interrupt {
+    match bank {
+        GpioBank::Gpio0 => Interrupt::GPIOINT0A,
+        GpioBank::Gpio1 => Interrupt::GPIOINT1A,
+        GpioBank::Gpio2 => Interrupt::GPIOINT2A,
+        GpioBank::Gpio3 => Interrupt::GPIOINT3A,
+    }
+}
+
+fn bank_clock(bank: GpioBank) -> clock::FuncClock {
+    match bank {
+        GpioBank::Gpio0 => clock::FuncClock::Gpio0,
+        GpioBank::Gpio1 => clock::FuncClock::Gpio1,
+        GpioBank::Gpio2 => clock::FuncClock::Gpio2,
+        GpioBank::Gpio3 => clock::FuncClock::Gpio3,
+    }
+}
+
+// An edge was detected on the pin, which wasn't handled yet
+pub fn edge_pending((pin, bank): GpioPin) -> bool {
+    read_addr(bank as u32 + GPIO_IRQSTATUS_RAW_0) & (1 << pin) != 0
+}
+
+pub fn register_interrupt(pin: GpioPin, edge: GpioEdge, handler: fn()) {
+    unsafe {
+        GPIO_INTERRUPT_HANDLERS[pin.1.index()][pin.0 as usize] = Some(handler);
+    }
+
+    enable_edge(pin, edge);
+}
+
+// Raises an interrupt on the edge without a handler, tasks waiting for the
+// edge are still woken
+pub fn enable_edge((pin, bank): GpioPin, edge: GpioEdge) {
+    let line = bank_interrupt(bank);
+    interrupts::enable_interrupt(line, Mode::IRQ, 1, handle_interrupts);
+
+    let base = bank as u32;
+    set_bit(base + GPIO_IRQSTATUS_SET0, pin);
+
+    // the edge of an earlier registration on the pin is replaced
+    match edge {
+        GpioEdge::Rising => {
+            set_bit(base + GPIO_RISINGDETECT, pin);
+            clear_bit(base + GPIO_FALLINGDETECT, pin);
+        }
+        GpioEdge::Falling => {
+            clear_bit(base + GPIO_RISINGDETECT, pin);
+            set_bit(base + GPIO_FALLINGDETECT, pin);
+        }
+        GpioEdge::Change => {
+            set_bit(base + GPIO_RISINGDETECT, pin);
+            set_bit(base + GPIO_FALLINGDETECT, pin)
+        }
+    }
 }
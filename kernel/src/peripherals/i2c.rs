@@ -1,22 +1,31 @@
-use core::{
-    arch::asm,
-    fmt::{self, Arguments, Write},
-};
+use core::{arch::asm, fmt};
 
 use crate::{
-    internals::clock::{self, FuncClock},
+    internals::{
+        clock::{self, FuncClock},
+        tasks::{scheduler, Event},
+    },
     interrupts::{self, Interrupt, Mode},
+    output::OutputSink,
 };
 use embedded_hal::i2c;
-use shared::{alloc::vec::Vec, i2c::PRINT_ADDRESS, sys::clear_bit};
+use shared::{
+    alloc::vec::Vec,
+    collections::ArrayVec,
+    i2c::{PRINT_ADDRESS, SCAN_ADDRESSES},
+    sys::clear_bit,
+};
 use shared::{
     i2c::I2cError,
+    interrupts::CriticalSection,
     sys::{read_addr, set_bit, write_addr},
 };
 
 const SYS_CLOCK: u32 = 48_000_000;
 const INTERNAL_CLOCK: u32 = 12_000_000;
-const OUTPUT_CLOCK: u32 = 100_000;
+
+// the module only works with an internal clock of 7 to 12 MHz
+const INTERNAL_CLOCK_RANGE: core::ops::RangeInclusive<u32> = 7_000_000..=12_000_000;
 
 const I2C_SYSC: u32 = 0x10;
 const I2C_IRQSTATUS_RAW: u32 = 0x24;
@@ -36,31 +45,152 @@ const I2C_SYSS: u32 = 0x90;
 const I2C_BUF: u32 = 0x94;
 const I2C_BUFSTAT: u32 = 0xC0;
 
+const RECEIVE_BUFFER_SIZE: usize = 256;
+
 const RECEIVE_THRESHOLD: u32 = 16;
 const TRANSMIT_THRESHOLD: u32 = 16;
 
 const TEST_ENABLE: u32 = 1 << 15;
 const TEST_MODE: u32 = 12;
 
+// the bus used by the syscalls and for printing
+pub const DEFAULT_MODULE: I2cModule = I2cModule::I2C2;
+
 pub fn initialize() {
-    let i2c = get_i2c();
+    initialize_module(DEFAULT_MODULE);
+}
+
+// The pins of the module have to be muxed to it before
+pub fn initialize_module(module: I2cModule) {
+    let i2c = get_i2c(module);
     i2c.initialize();
 }
 
 #[allow(static_mut_refs)]
-pub fn get_i2c() -> &'static mut I2C {
-    unsafe { &mut I2C }
+pub fn get_i2c(module: I2cModule) -> &'static mut I2C {
+    unsafe { &mut I2C[module.index()] }
+}
+
+static mut I2C: [I2C; 3] = [
+    I2C::new(I2cModule::I2C0, I2cClock::DEFAULT),
+    I2C::new(I2cModule::I2C1, I2cClock::DEFAULT),
+    I2C::new(I2cModule::I2C2, I2cClock::DEFAULT),
+];
+
+static PRINT_TARGET: CriticalSection<u8> = CriticalSection::new(PRINT_ADDRESS);
+
+pub fn set_print_address(address: u8) {
+    *PRINT_TARGET.lock() = address;
 }
 
-static mut I2C: I2C = I2C::new(I2cModule::I2C2);
+pub fn print_address() -> u8 {
+    *PRINT_TARGET.lock()
+}
+
+// SCL frequency of the bus
+#[derive(Clone, Copy, PartialEq)]
+pub enum I2cSpeed {
+    Standard100k,
+    Fast400k,
+}
+
+impl I2cSpeed {
+    pub const fn frequency(&self) -> u32 {
+        match self {
+            I2cSpeed::Standard100k => 100_000,
+            I2cSpeed::Fast400k => 400_000,
+        }
+    }
+}
+
+// Clock tree of an I2C module, the functional clock is divided down by the
+// prescaler to the internal clock, from which SCL is generated
+#[derive(Clone, Copy)]
+pub struct I2cClock {
+    functional: u32,
+    internal: u32,
+    output: u32,
+}
+
+impl I2cClock {
+    pub const DEFAULT: I2cClock = I2cClock::with_speed(I2cSpeed::Standard100k);
+
+    pub const fn with_speed(speed: I2cSpeed) -> Self {
+        I2cClock::new(SYS_CLOCK, INTERNAL_CLOCK, speed.frequency())
+    }
+
+    // Panics if the internal clock, which the functional clock can be divided
+    // down to, is out of range. Constructing the clock in a const context
+    // turns this into a build error.
+    pub const fn new(functional: u32, internal: u32, output: u32) -> Self {
+        assert!(internal > 0 && output > 0 && internal <= functional);
+
+        let clock = I2cClock {
+            functional,
+            internal,
+            output,
+        };
+
+        let actual = functional / (clock.prescaler() + 1);
+        assert!(
+            actual >= *INTERNAL_CLOCK_RANGE.start() && actual <= *INTERNAL_CLOCK_RANGE.end(),
+            "i2c internal clock out of range"
+        );
+        assert!(clock.prescaler() <= 0xFF, "i2c prescaler out of range");
+        assert!(
+            clock.low_period() > 7 && clock.high_period() > 5,
+            "i2c output clock too high"
+        );
+
+        clock
+    }
+
+    pub const fn prescaler(&self) -> u32 {
+        self.functional / self.internal - 1
+    }
+
+    // internal clock cycles per period of SCL
+    const fn period(&self) -> u32 {
+        self.functional / (self.prescaler() + 1) / self.output
+    }
+
+    // fast mode requires SCL to stay low about twice as long as high, standard
+    // mode uses an even duty cycle
+    const fn low_period(&self) -> u32 {
+        if self.output > I2cSpeed::Standard100k.frequency() {
+            self.period() * 2 / 3
+        } else {
+            self.period() / 2
+        }
+    }
+
+    const fn high_period(&self) -> u32 {
+        self.period() - self.low_period()
+    }
+
+    // the module stretches the low and high phase by 7 and 5 cycles
+    pub const fn scll(&self) -> u32 {
+        self.low_period() - 7
+    }
+
+    pub const fn sclh(&self) -> u32 {
+        self.high_period() - 5
+    }
+}
 
 pub struct I2C {
     module: I2cModule,
+    clock: I2cClock,
     ready: bool,
     error: Option<I2cError>,
-    receive_buffer: Vec<u8>,
+    receive_buffer: ArrayVec<u8, RECEIVE_BUFFER_SIZE>,
     transmit_buffer: Vec<u8>,
     transmit_index: usize,
+    // handle of the transfer started by `start_write`, which is in flight
+    pending: Option<u32>,
+    // handle and result of the last completed `start_write` transfer
+    completed: Option<(u32, I2cError)>,
+    next_handle: u32,
 }
 
 impl i2c::I2c for I2C {
@@ -69,11 +199,15 @@ impl i2c::I2c for I2C {
         address: u8,
         operations: &mut [i2c::Operation<'_>],
     ) -> Result<(), Self::Error> {
-        let mode: I2cMode = operations.first().unwrap().into();
+        // a transfer started with `start_write` has to complete first
+        while self.pending.is_some() {
+            unsafe {
+                asm!("nop");
+            }
+        }
 
         self.enable();
 
-        self.set_mode(mode);
         self.set_slave(address);
         self.clear_buffer();
 
@@ -81,36 +215,71 @@ impl i2c::I2c for I2C {
 
         while self.busy() {}
 
-        for operation in operations {
-            if let i2c::Operation::Write(buffer) = operation {
-                if buffer.is_empty() {
-                    continue;
-                }
+        // adjacent operations of the same direction are sent as one segment,
+        // a change of direction issues a repeated start without releasing the
+        // bus in between
+        let mut remaining = &mut operations[..];
+        while !remaining.is_empty() {
+            let mode: I2cMode = (&remaining[0]).into();
+            let count = remaining
+                .iter()
+                .take_while(|operation| I2cMode::from(*operation) == mode)
+                .count();
+            let (segment, rest) = remaining.split_at_mut(count);
+            remaining = rest;
+
+            let len: usize = segment.iter().map(operation_len).sum();
+
+            if len == 0 {
+                continue;
+            }
 
-                for i in 0..buffer.len() {
-                    self.transmit_buffer.push(buffer[i]);
+            if mode == I2cMode::Receiver && len > RECEIVE_BUFFER_SIZE {
+                if started {
+                    self.stop();
                 }
+                self.disable();
 
-                self.set_count(buffer.len() as u32);
-                self.ready = false;
+                return Err(I2cError::Overrun);
+            }
 
-                if !started {
-                    self.start();
-                    started = true;
+            for operation in segment.iter() {
+                if let i2c::Operation::Write(buffer) = operation {
+                    self.transmit_buffer.extend_from_slice(buffer);
                 }
+            }
 
-                self.enable_interrupts(I2cMode::Transmitter);
-                self.wait_ready();
-                self.disable_interrupts(I2cMode::Transmitter);
+            self.set_mode(mode);
+            self.set_count(len as u32);
+            self.ready = false;
 
-                if let Some(error) = self.error {
-                    self.stop();
-                    self.disable();
+            // a start while the bus is held is the repeated start
+            self.start();
+            started = true;
+
+            self.enable_interrupts(mode);
+            self.wait_ready();
+            self.disable_interrupts(mode);
+
+            if let Some(error) = self.error {
+                self.stop();
+                self.disable();
 
-                    self.error = None;
+                self.error = None;
 
-                    return Err(error);
+                return Err(error);
+            }
+
+            if mode == I2cMode::Receiver {
+                let mut received = &self.receive_buffer[..];
+                for operation in segment.iter_mut() {
+                    if let i2c::Operation::Read(buffer) = operation {
+                        let len = received.len().min(buffer.len());
+                        buffer[..len].copy_from_slice(&received[..len]);
+                        received = &received[len..];
+                    }
                 }
+                self.receive_buffer.clear();
             }
         }
 
@@ -121,48 +290,34 @@ impl i2c::I2c for I2C {
     }
 }
 
+impl OutputSink for I2C {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        self.write(print_address(), data).map_err(|_| fmt::Error)
+    }
+}
+
 impl fmt::Write for I2C {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        match self.write(PRINT_ADDRESS, s.as_bytes()) {
+        match self.write(print_address(), s.as_bytes()) {
             Ok(_) => Ok(()),
             Err(_) => Err(fmt::Error),
         }
     }
 }
 
-pub fn print(args: Arguments<'_>) {
-    let i2c = get_i2c();
-    i2c.write_fmt(args).unwrap();
-}
-
-pub fn println(args: Arguments<'_>) {
-    let i2c = get_i2c();
-    i2c.write_fmt(format_args!("{}\n", args)).unwrap();
-}
-
-#[macro_export]
-macro_rules! print {
-    ($($arg:tt)*) => {
-        $crate::i2c::print(format_args!($($arg)*))
-    }
-}
-
-#[macro_export]
-macro_rules! println {
-    ($($arg:tt)*) => {
-        $crate::i2c::println(format_args!($($arg)*))
-    }
-}
-
 impl I2C {
-    const fn new(module: I2cModule) -> Self {
+    const fn new(module: I2cModule, clock: I2cClock) -> Self {
         Self {
             module,
+            clock,
             ready: true,
             error: None,
-            receive_buffer: Vec::new(),
+            receive_buffer: ArrayVec::new(),
             transmit_buffer: Vec::new(),
             transmit_index: 0,
+            pending: None,
+            completed: None,
+            next_handle: 1,
         }
     }
 
@@ -171,10 +326,9 @@ impl I2C {
     }
 
     fn initialize(&self) {
-        clock::enable(FuncClock::I2C2);
+        clock::enable(self.module.clock());
 
-        interrupts::enable_interrupt(Interrupt::I2C2INT, Mode::IRQ, 2); // enable irq
-        interrupts::register_handler(irq_handler, Interrupt::I2C2INT); // register handler
+        interrupts::enable_interrupt(self.module.interrupt(), Mode::IRQ, 2, irq_handler); // enable irq
 
         // config
         self.soft_reset();
@@ -230,6 +384,7 @@ impl I2C {
             self.ready = true;
 
             write_addr(self.base() + I2C_IRQSTATUS, I2cInterrupt::ARDY as u32);
+            self.finish_write();
             return;
         }
 
@@ -238,9 +393,96 @@ impl I2C {
             self.ready = true;
 
             write_addr(self.base() + I2C_IRQSTATUS, I2cInterrupt::NACK as u32);
+            self.finish_write();
+        }
+    }
+
+    // Starts writing `data` and returns without waiting for the transfer.
+    // The returned handle is passed to `wait`, which reports the result.
+    pub fn start_write(&mut self, address: u8, data: &[u8]) -> Result<u32, I2cError> {
+        if self.pending.is_some() || !self.idle() {
+            return Err(I2cError::Busy);
+        }
+
+        self.enable();
+        self.set_slave(address);
+        self.clear_buffer();
+        self.transmit_buffer.extend_from_slice(data);
+
+        let handle = self.next_handle;
+        self.next_handle = self.next_handle.wrapping_add(1).max(1);
+        self.pending = Some(handle);
+
+        if data.is_empty() {
+            self.complete(Ok(()));
+            return Ok(handle);
+        }
+
+        self.set_mode(I2cMode::Transmitter);
+        self.set_count(data.len() as u32);
+        self.ready = false;
+        self.start();
+        self.enable_interrupts(I2cMode::Transmitter);
+
+        Ok(handle)
+    }
+
+    // The result of a transfer started by `start_write`, None while it is
+    // still in flight or if the handle is unknown
+    pub fn result(&self, handle: u32) -> Option<I2cError> {
+        match self.completed {
+            Some((completed, result)) if completed == handle => Some(result),
+            _ => None,
         }
     }
 
+    pub fn in_flight(&self, handle: u32) -> bool {
+        self.pending == Some(handle)
+    }
+
+    fn finish_write(&mut self) {
+        if self.pending.is_none() {
+            return;
+        }
+
+        self.disable_interrupts(I2cMode::Transmitter);
+
+        let result = match self.error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        };
+        self.complete(result);
+    }
+
+    fn complete(&mut self, result: Result<(), I2cError>) {
+        self.stop();
+        self.disable();
+
+        let Some(handle) = self.pending.take() else {
+            return;
+        };
+        let result = result.err().unwrap_or(I2cError::Success);
+        self.completed = Some((handle, result));
+
+        scheduler().wake_all(Event::I2cDone(handle), result as u32);
+    }
+
+    // The clocks may only be changed while the module is disabled, so this
+    // waits for a running transfer to finish first
+    pub fn set_speed(&mut self, speed: I2cSpeed) {
+        while self.pending.is_some() || !self.idle() {
+            unsafe {
+                asm!("nop");
+            }
+        }
+
+        self.clock = I2cClock::with_speed(speed);
+
+        self.disable();
+        self.init_clocks();
+        self.enable();
+    }
+
     fn soft_reset(&self) {
         write_addr(
             self.base() + I2C_SYSC,
@@ -249,14 +491,10 @@ impl I2C {
     }
 
     fn init_clocks(&self) {
-        let prescaler = (SYS_CLOCK / INTERNAL_CLOCK) - 1;
-        write_addr(self.base() + I2C_PSC, prescaler);
-
-        let mut divider = INTERNAL_CLOCK / OUTPUT_CLOCK;
-        divider /= 2;
+        write_addr(self.base() + I2C_PSC, self.clock.prescaler());
 
-        write_addr(self.base() + I2C_SCLL, divider - 7);
-        write_addr(self.base() + I2C_SCLH, divider - 5);
+        write_addr(self.base() + I2C_SCLL, self.clock.scll());
+        write_addr(self.base() + I2C_SCLH, self.clock.sclh());
     }
 
     fn set_own_address(&self) {
@@ -276,7 +514,7 @@ impl I2C {
     }
 
     fn set_mode(&self, mode: I2cMode) {
-        let value = read_addr(self.base() + I2C_CON);
+        let value = read_addr(self.base() + I2C_CON) & !(1 << 9);
         let is_transmitter = matches!(mode, I2cMode::Transmitter);
         write_addr(
             self.base() + I2C_CON,
@@ -314,10 +552,74 @@ impl I2C {
         <Self as i2c::I2c>::write(self, address, data)
     }
 
+    pub fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), I2cError> {
+        <Self as i2c::I2c>::read(self, address, buffer)
+    }
+
+    // Writes `data`, e.g. a register address, then reads into `buffer` after a
+    // repeated start, without releasing the bus in between
+    pub fn write_read(
+        &mut self,
+        address: u8,
+        data: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), I2cError> {
+        <Self as i2c::I2c>::write_read(self, address, data, buffer)
+    }
+
+    // Addresses of the devices which acknowledge a zero-length write
+    pub fn scan(&mut self) -> Vec<u8> {
+        let mut found = Vec::new();
+
+        for address in SCAN_ADDRESSES {
+            if self.probe(address) {
+                found.push(address);
+            }
+        }
+
+        found
+    }
+
+    // Reads a single byte, the stop is requested together with the start, so
+    // the bus is released whether the device acknowledges or not. A count of
+    // 0 isn't a transfer without data on this controller.
+    fn probe(&mut self, address: u8) -> bool {
+        // a transfer started with `start_write` has to complete first
+        while self.pending.is_some() {
+            unsafe {
+                asm!("nop");
+            }
+        }
+
+        self.enable();
+        self.set_slave(address);
+        self.clear_buffer();
+
+        while self.busy() {}
+
+        self.set_mode(I2cMode::Receiver);
+        self.set_count(1);
+        self.ready = false;
+
+        self.enable_interrupts(I2cMode::Receiver);
+        self.start_stop();
+        self.wait_ready();
+        self.disable_interrupts(I2cMode::Receiver);
+
+        // wait for the stop condition before the next address
+        while self.busy() {}
+        self.disable();
+
+        self.error.take().is_none()
+    }
+
     fn clear_buffer(&mut self) {
         self.transmit_buffer.clear();
         self.transmit_index = 0;
         self.clear_transmit_fifo();
+
+        self.receive_buffer.clear();
+        self.clear_receive_fifo();
     }
 
     pub fn write_str(&mut self, address: u8, data: &str) -> Result<(), I2cError> {
@@ -352,6 +654,15 @@ impl I2C {
         set_bit(self.base() + I2C_BUF, 6);
     }
 
+    fn clear_receive_fifo(&self) {
+        set_bit(self.base() + I2C_BUF, 14);
+    }
+
+    // no transfer is in progress and the bus is released
+    pub fn idle(&self) -> bool {
+        self.ready && !self.busy()
+    }
+
     fn busy(&self) -> bool {
         let value = read_addr(self.base() + I2C_IRQSTATUS_RAW);
         value & (1 << 12) != 0
@@ -375,20 +686,29 @@ impl I2C {
         write_addr(self.base() + I2C_CON, value | 0x1);
     }
 
+    fn start_stop(&self) {
+        let value = read_addr(self.base() + I2C_CON);
+        write_addr(self.base() + I2C_CON, value | 0x3);
+    }
+
     fn stop(&mut self) {
         let value = read_addr(self.base() + I2C_CON);
         write_addr(self.base() + I2C_CON, value | 0x2);
     }
 
+    // The controller may ask for more bytes than were queued, they are simply
+    // not written instead of panicking in the interrupt handler
     fn write_data(&mut self) {
-        let data = self.transmit_buffer.get(self.transmit_index).unwrap();
-        write_addr(self.base() + I2C_DATA, *data as u32);
+        let Some(&data) = self.transmit_buffer.get(self.transmit_index) else {
+            return;
+        };
+        write_addr(self.base() + I2C_DATA, data as u32);
         self.transmit_index += 1;
     }
 
     fn read_data(&mut self) {
         let data = read_addr(self.base() + I2C_DATA) as u8;
-        self.receive_buffer.push(data);
+        let _ = self.receive_buffer.push(data);
     }
 
     fn transmit_bytes_available(&self) -> u32 {
@@ -405,16 +725,56 @@ impl i2c::ErrorType for I2C {
 }
 
 fn irq_handler() {
-    let i2c = get_i2c();
-    i2c.irq_handler()
+    let module = interrupts::current().and_then(I2cModule::try_new);
+
+    if let Some(module) = module {
+        get_i2c(module).irq_handler()
+    }
 }
 
 #[derive(Clone, Copy)]
-enum I2cModule {
+pub enum I2cModule {
+    I2C0 = 0x44E0_B000,
+    I2C1 = 0x4802_A000,
     I2C2 = 0x4819_C000,
 }
 
-#[allow(unused)]
+impl I2cModule {
+    fn try_new(interrupt: Interrupt) -> Option<Self> {
+        match interrupt {
+            Interrupt::I2C0INT => Some(I2cModule::I2C0),
+            Interrupt::I2C1INT => Some(I2cModule::I2C1),
+            Interrupt::I2C2INT => Some(I2cModule::I2C2),
+            _ => None,
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            I2cModule::I2C0 => 0,
+            I2cModule::I2C1 => 1,
+            I2cModule::I2C2 => 2,
+        }
+    }
+
+    fn clock(&self) -> FuncClock {
+        match self {
+            I2cModule::I2C0 => FuncClock::I2C0,
+            I2cModule::I2C1 => FuncClock::I2C1,
+            I2cModule::I2C2 => FuncClock::I2C2,
+        }
+    }
+
+    fn interrupt(&self) -> Interrupt {
+        match self {
+            I2cModule::I2C0 => Interrupt::I2C0INT,
+            I2cModule::I2C1 => Interrupt::I2C1INT,
+            I2cModule::I2C2 => Interrupt::I2C2INT,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 enum I2cMode {
     Transmitter,
     Receiver,
@@ -429,6 +789,13 @@ impl From<&i2c::Operation<'_>> for I2cMode {
     }
 }
 
+fn operation_len(operation: &i2c::Operation<'_>) -> usize {
+    match operation {
+        i2c::Operation::Write(buffer) => buffer.len(),
+        i2c::Operation::Read(buffer) => buffer.len(),
+    }
+}
+
 impl I2cMode {
     fn interrupts(&self) -> &[I2cInterrupt] {
         match self {
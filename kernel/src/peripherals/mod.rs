@@ -1,2 +1,7 @@
+pub mod adc;
 pub mod gpio;
 pub mod i2c;
+pub mod pwm;
+pub mod spi;
+pub mod uart;
+pub mod watchdog;
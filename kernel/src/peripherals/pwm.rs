@@ -0,0 +1,142 @@
+use core::ptr;
+
+use crate::{
+    internals::clock::{self, FuncClock},
+    pinmux,
+};
+use shared::{pwm::PwmChannel, sys::set_bit};
+
+// the time base counts the 100 MHz SYSCLKOUT, divided by the prescaler
+const SYS_CLOCK_MHZ: u32 = 100;
+const DEFAULT_PERIOD_NS: u32 = 1_000_000;
+
+// time base clock enables of the three PWMSS in the control module
+const PWMSS_CTRL: u32 = 0x44E1_0664;
+
+// the ePWM registers are 16 bits wide
+const EPWM_TBCTL: u32 = 0x00;
+const EPWM_TBCNT: u32 = 0x08;
+const EPWM_TBPRD: u32 = 0x0A;
+const EPWM_CMPCTL: u32 = 0x0E;
+const EPWM_CMPA: u32 = 0x12;
+const EPWM_CMPB: u32 = 0x14;
+const EPWM_AQCTLA: u32 = 0x16;
+const EPWM_AQCTLB: u32 = 0x18;
+
+// up count mode, no phase loading, sync output disabled and free run when
+// debugging. The prescaler is added in bits 10 to 12.
+const TBCTL_UP_COUNT: u16 = (0b11 << 4) | (0b10 << 14);
+const TBCTL_CLKDIV_SHIFT: u16 = 10;
+const MAX_CLKDIV: u32 = 7;
+
+// the output goes high when the counter is zero and low when it reaches the
+// compare value, so the compare value sets the duty cycle
+const AQCTLA_HIGH_TO_CMPA: u16 = 0b10 | (0b01 << 4);
+const AQCTLB_HIGH_TO_CMPB: u16 = 0b10 | (0b01 << 8);
+
+static mut INITIALIZED: [bool; 3] = [false; 3];
+
+// Routes the output to its pin, the module is set up with the default period
+// when the first of its outputs is initialized
+pub fn init(channel: PwmChannel) {
+    let module = channel.module();
+
+    pinmux::configure_pwm(channel);
+
+    if unsafe { INITIALIZED[module] } {
+        return;
+    }
+
+    clock::enable(clock_of(module));
+    set_bit(PWMSS_CTRL, module as u32);
+
+    let base = base_of(module);
+    write_reg(base + EPWM_CMPCTL, 0);
+    write_reg(base + EPWM_AQCTLA, AQCTLA_HIGH_TO_CMPA);
+    write_reg(base + EPWM_AQCTLB, AQCTLB_HIGH_TO_CMPB);
+    write_reg(base + EPWM_CMPA, 0);
+    write_reg(base + EPWM_CMPB, 0);
+    write_reg(base + EPWM_TBCNT, 0);
+
+    unsafe { INITIALIZED[module] = true };
+
+    set_period(channel, DEFAULT_PERIOD_NS);
+}
+
+// Sets the period of both outputs of the channel's module, the duty cycles
+// are kept. Returns false if the period is too long even for the largest
+// prescaler.
+pub fn set_period(channel: PwmChannel, ns: u32) -> bool {
+    let ticks = ns / 1000 * SYS_CLOCK_MHZ + ns % 1000 * SYS_CLOCK_MHZ / 1000;
+
+    // the prescaler divides by powers of two
+    let Some(clkdiv) = (0..=MAX_CLKDIV).find(|div| ticks >> div <= u16::MAX as u32) else {
+        return false;
+    };
+    let period = ((ticks >> clkdiv).max(1)) as u16;
+
+    let base = base_of(channel.module());
+    let duty_a = duty(base, EPWM_CMPA);
+    let duty_b = duty(base, EPWM_CMPB);
+
+    write_reg(
+        base + EPWM_TBCTL,
+        TBCTL_UP_COUNT | ((clkdiv as u16) << TBCTL_CLKDIV_SHIFT),
+    );
+    write_reg(base + EPWM_TBPRD, period);
+
+    write_reg(base + EPWM_CMPA, compare_value(period, duty_a));
+    write_reg(base + EPWM_CMPB, compare_value(period, duty_b));
+
+    true
+}
+
+// Sets the share of the period in which the output is high, values above
+// 100 are treated as 100
+pub fn set_duty(channel: PwmChannel, percent: u8) {
+    let base = base_of(channel.module());
+    let period = read_reg(base + EPWM_TBPRD);
+
+    let compare = match channel.is_a() {
+        true => EPWM_CMPA,
+        false => EPWM_CMPB,
+    };
+
+    write_reg(base + compare, compare_value(period, percent.min(100)));
+}
+
+// Duty cycle in percent of the output driven by the compare register
+fn duty(base: u32, compare: u32) -> u8 {
+    match read_reg(base + EPWM_TBPRD) {
+        0 => 0,
+        period => (read_reg(base + compare) as u32 * 100 / period as u32) as u8,
+    }
+}
+
+fn compare_value(period: u16, percent: u8) -> u16 {
+    (period as u32 * percent as u32 / 100) as u16
+}
+
+fn base_of(module: usize) -> u32 {
+    match module {
+        0 => 0x4830_0200,
+        1 => 0x4830_2200,
+        _ => 0x4830_4200,
+    }
+}
+
+fn clock_of(module: usize) -> FuncClock {
+    match module {
+        0 => FuncClock::Epwmss0,
+        1 => FuncClock::Epwmss1,
+        _ => FuncClock::Epwmss2,
+    }
+}
+
+fn write_reg(address: u32, value: u16) {
+    unsafe { ptr::write_volatile(address as *mut u16, value) };
+}
+
+fn read_reg(address: u32) -> u16 {
+    unsafe { ptr::read_volatile(address as *const u16) }
+}
@@ -0,0 +1,165 @@
+use core::convert::Infallible;
+
+use crate::{
+    internals::clock::{self, FuncClock},
+    pinmux,
+};
+use embedded_hal::spi::{self, Phase, Polarity, MODE_0};
+use shared::sys::{read_addr, read_bit, set_bit, write_addr};
+
+const MCSPI0: u32 = 0x4803_0000;
+
+const MCSPI_SYSCONFIG: u32 = 0x110;
+const MCSPI_SYSSTATUS: u32 = 0x114;
+const MCSPI_MODULCTRL: u32 = 0x128;
+const MCSPI_CH0CONF: u32 = 0x12C;
+const MCSPI_CH0STAT: u32 = 0x130;
+const MCSPI_CH0CTRL: u32 = 0x134;
+const MCSPI_TX0: u32 = 0x138;
+const MCSPI_RX0: u32 = 0x13C;
+
+const FUNCTIONAL_CLOCK: u32 = 48_000_000;
+const DEFAULT_SPEED: u32 = 1_000_000;
+const MAX_CLOCK_DIVIDER: u32 = 15;
+
+// single channel master
+const MODULCTRL_SINGLE: u32 = 1 << 0;
+
+const CONF_PHA: u32 = 1 << 0;
+const CONF_POL: u32 = 1 << 1;
+const CONF_CLKD_SHIFT: u32 = 2;
+// chip select is active low
+const CONF_EPOL: u32 = 1 << 6;
+const CONF_WORD_LENGTH_8: u32 = 7 << 7;
+// transmits on D1 and receives on D0
+const CONF_DPE0: u32 = 1 << 16;
+
+const STAT_RX_FULL: u32 = 0;
+const STAT_TX_EMPTY: u32 = 1;
+
+#[allow(static_mut_refs)]
+pub fn get_spi() -> &'static mut Spi {
+    unsafe { &mut SPI }
+}
+
+static mut SPI: Spi = Spi::new(MCSPI0);
+
+pub struct Spi {
+    base: u32,
+    initialized: bool,
+}
+
+impl Spi {
+    const fn new(base: u32) -> Self {
+        Spi {
+            base,
+            initialized: false,
+        }
+    }
+
+    pub fn initialized(&self) -> bool {
+        self.initialized
+    }
+
+    // Sets up channel 0 as master, the clock runs at the fastest speed the
+    // divider allows which doesn't exceed `speed`
+    pub fn init(&mut self, mode: spi::Mode, speed: u32) {
+        clock::enable(FuncClock::Spi0);
+        pinmux::configure_spi();
+
+        set_bit(self.base + MCSPI_SYSCONFIG, 1);
+        while !read_bit(self.base + MCSPI_SYSSTATUS, 0) {}
+
+        write_addr(self.base + MCSPI_MODULCTRL, MODULCTRL_SINGLE);
+
+        let mut conf = CONF_EPOL | CONF_WORD_LENGTH_8 | CONF_DPE0;
+        conf |= clock_divider(speed) << CONF_CLKD_SHIFT;
+        if mode.phase == Phase::CaptureOnSecondTransition {
+            conf |= CONF_PHA;
+        }
+        if mode.polarity == Polarity::IdleHigh {
+            conf |= CONF_POL;
+        }
+        write_addr(self.base + MCSPI_CH0CONF, conf);
+
+        write_addr(self.base + MCSPI_CH0CTRL, 0x1);
+
+        self.initialized = true;
+    }
+
+    // Sets up the bus in mode 0 at 1 MHz, unless it was set up already
+    pub fn init_default(&mut self) {
+        if !self.initialized {
+            self.init(MODE_0, DEFAULT_SPEED);
+        }
+    }
+
+    // Sends every byte of the buffer and replaces it with the byte received
+    // at the same time
+    pub fn exchange(&mut self, buffer: &mut [u8]) {
+        for byte in buffer.iter_mut() {
+            *byte = self.exchange_byte(*byte);
+        }
+    }
+
+    fn exchange_byte(&self, byte: u8) -> u8 {
+        while !read_bit(self.base + MCSPI_CH0STAT, STAT_TX_EMPTY) {}
+        write_addr(self.base + MCSPI_TX0, byte as u32);
+
+        while !read_bit(self.base + MCSPI_CH0STAT, STAT_RX_FULL) {}
+        read_addr(self.base + MCSPI_RX0) as u8
+    }
+}
+
+impl spi::ErrorType for Spi {
+    type Error = Infallible;
+}
+
+impl spi::SpiBus for Spi {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for word in words.iter_mut() {
+            *word = self.exchange_byte(0);
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        for word in words {
+            self.exchange_byte(*word);
+        }
+
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        // the shorter buffer is padded with zeros, or the received bytes are
+        // dropped
+        for index in 0..read.len().max(write.len()) {
+            let received = self.exchange_byte(write.get(index).copied().unwrap_or(0));
+            if let Some(word) = read.get_mut(index) {
+                *word = received;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        self.exchange(words);
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // every byte was received, so nothing is left to send
+        Ok(())
+    }
+}
+
+// The divider is a power of two
+fn clock_divider(speed: u32) -> u32 {
+    (0..MAX_CLOCK_DIVIDER)
+        .find(|divider| FUNCTIONAL_CLOCK >> divider <= speed)
+        .unwrap_or(MAX_CLOCK_DIVIDER)
+}
@@ -0,0 +1,108 @@
+use core::fmt::{self, Arguments, Write};
+
+use crate::{
+    internals::clock::{self, FuncClock},
+    output::OutputSink,
+};
+use shared::sys::{read_bit, set_bit, write_addr};
+
+const UART0: u32 = 0x44E0_9000;
+
+const UART_THR: u32 = 0x0;
+const UART_DLL: u32 = 0x0;
+const UART_DLH: u32 = 0x4;
+const UART_FCR: u32 = 0x8;
+const UART_LCR: u32 = 0xC;
+const UART_LSR: u32 = 0x14;
+const UART_MDR1: u32 = 0x20;
+const UART_SYSC: u32 = 0x54;
+const UART_SYSS: u32 = 0x58;
+
+const FUNCTIONAL_CLOCK: u32 = 48_000_000;
+const BAUD_RATE: u32 = 115_200;
+// the module samples every bit 16 times
+const DIVISOR: u32 = FUNCTIONAL_CLOCK / (16 * BAUD_RATE);
+
+// word length of 8 bits, no parity and one stop bit
+const LCR_8N1: u32 = 0b11;
+const LCR_DIVISOR_LATCH: u32 = 1 << 7;
+const LSR_THR_EMPTY: u32 = 5;
+
+const MODE_UART_16X: u32 = 0x0;
+const MODE_DISABLE: u32 = 0x7;
+
+pub fn initialize() {
+    let uart = get_uart();
+    uart.initialize();
+}
+
+#[allow(static_mut_refs)]
+pub fn get_uart() -> &'static mut Uart {
+    unsafe { &mut UART }
+}
+
+static mut UART: Uart = Uart::new(UART0);
+
+pub struct Uart {
+    base: u32,
+}
+
+impl Uart {
+    const fn new(base: u32) -> Self {
+        Uart { base }
+    }
+
+    fn initialize(&self) {
+        clock::enable(FuncClock::Uart0);
+
+        set_bit(self.base + UART_SYSC, 1);
+        while !read_bit(self.base + UART_SYSS, 0) {}
+
+        // the baud rate can only be changed while the module is disabled
+        write_addr(self.base + UART_MDR1, MODE_DISABLE);
+
+        write_addr(self.base + UART_LCR, LCR_DIVISOR_LATCH);
+        write_addr(self.base + UART_DLL, DIVISOR & 0xFF);
+        write_addr(self.base + UART_DLH, (DIVISOR >> 8) & 0x3F);
+        write_addr(self.base + UART_LCR, LCR_8N1);
+
+        // enable and clear both fifos
+        write_addr(self.base + UART_FCR, 0b111);
+
+        write_addr(self.base + UART_MDR1, MODE_UART_16X);
+    }
+
+    pub fn write_byte(&self, byte: u8) {
+        while !read_bit(self.base + UART_LSR, LSR_THR_EMPTY) {}
+
+        write_addr(self.base + UART_THR, byte as u32);
+    }
+
+    pub fn write(&self, data: &[u8]) {
+        for byte in data {
+            self.write_byte(*byte);
+        }
+    }
+}
+
+impl OutputSink for Uart {
+    fn write_bytes(&mut self, data: &[u8]) -> fmt::Result {
+        self.write(data);
+        Ok(())
+    }
+}
+
+impl fmt::Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+pub fn print(args: Arguments<'_>) {
+    get_uart().write_fmt(args).unwrap();
+}
+
+pub fn println(args: Arguments<'_>) {
+    get_uart().write_fmt(format_args!("{}\n", args)).unwrap();
+}
@@ -0,0 +1,121 @@
+// WDT1 resets the whole board once its counter overflows, unless it is
+// kicked in time. Once enabled with `init`, some task has to call `kick`
+// (or issue Syscall::WatchdogKick) more often than the timeout, a hanging
+// system is then restarted instead of staying stuck.
+
+use crate::internals::{
+    clock::{self, FuncClock},
+    sysclock,
+};
+use shared::sys::{read_addr, write_addr};
+
+const WDT1: u32 = 0x44E3_5000;
+
+const WDT_WCLR: u32 = 0x24;
+const WDT_WCRR: u32 = 0x28;
+const WDT_WLDR: u32 = 0x2C;
+const WDT_WTGR: u32 = 0x30;
+const WDT_WWPS: u32 = 0x34;
+const WDT_WSPR: u32 = 0x48;
+
+// write pending bits of WWPS, a register can't be written again while its
+// last write is still being synchronized to the 32 kHz clock
+const PEND_WCLR: u32 = 1 << 0;
+const PEND_WCRR: u32 = 1 << 1;
+const PEND_WLDR: u32 = 1 << 2;
+const PEND_WTGR: u32 = 1 << 3;
+const PEND_WSPR: u32 = 1 << 4;
+
+// the counter runs at 32768 Hz without prescaler
+const CLOCK_HZ: u64 = 32_768;
+
+static mut ENABLED: bool = false;
+static mut TRIGGER: u32 = 0;
+
+// Milliseconds since boot of the last kick and the longest time between two
+// kicks, only tracked in debug builds
+static mut LAST_KICK: u32 = 0;
+static mut MAX_INTERVAL: u32 = 0;
+
+// Starts the watchdog, which resets the board if it isn't kicked within
+// `timeout_ms` milliseconds
+pub fn init(timeout_ms: u32) {
+    clock::enable(FuncClock::Wdt1);
+
+    // the timeout can only be changed while the watchdog is stopped
+    disable();
+
+    write_addr(WDT1 + WDT_WCLR, 0);
+    wait_pending(PEND_WCLR);
+
+    let ticks = (timeout_ms as u64 * CLOCK_HZ / 1000).clamp(1, u32::MAX as u64) as u32;
+    let reload = 0u32.wrapping_sub(ticks);
+
+    write_addr(WDT1 + WDT_WLDR, reload);
+    wait_pending(PEND_WLDR);
+    write_addr(WDT1 + WDT_WCRR, reload);
+    wait_pending(PEND_WCRR);
+
+    write_magic(0xBBBB, 0x4444);
+
+    // the first interval starts once the watchdog runs
+    if cfg!(debug_assertions) && sysclock::running() {
+        unsafe { LAST_KICK = sysclock::millis() };
+    }
+
+    unsafe { ENABLED = true };
+}
+
+pub fn enabled() -> bool {
+    unsafe { ENABLED }
+}
+
+// Reloads the counter, every kick has to write a new value to the trigger
+// register
+pub fn kick() {
+    let trigger = unsafe {
+        TRIGGER = TRIGGER.wrapping_add(1);
+        TRIGGER
+    };
+
+    write_addr(WDT1 + WDT_WTGR, trigger);
+    wait_pending(PEND_WTGR);
+
+    record_kick();
+}
+
+fn record_kick() {
+    if cfg!(debug_assertions) && sysclock::running() {
+        let now = sysclock::millis();
+
+        unsafe {
+            MAX_INTERVAL = MAX_INTERVAL.max(now.wrapping_sub(LAST_KICK));
+            LAST_KICK = now;
+        }
+    }
+}
+
+// Longest time between two kicks, a value close to the timeout means some code
+// path nearly failed to kick the watchdog. Always 0 in release builds.
+pub fn max_interval_ms() -> u32 {
+    unsafe { MAX_INTERVAL }
+}
+
+pub fn disable() {
+    write_magic(0xAAAA, 0x5555);
+
+    unsafe { ENABLED = false };
+}
+
+// The watchdog is started and stopped by writing a sequence of two values
+// to WSPR
+fn write_magic(first: u32, second: u32) {
+    write_addr(WDT1 + WDT_WSPR, first);
+    wait_pending(PEND_WSPR);
+    write_addr(WDT1 + WDT_WSPR, second);
+    wait_pending(PEND_WSPR);
+}
+
+fn wait_pending(mask: u32) {
+    while read_addr(WDT1 + WDT_WWPS) & mask != 0 {}
+}
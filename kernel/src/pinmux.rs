@@ -1,4 +1,4 @@
-use shared::sys::write_addr;
+use shared::{pwm::PwmChannel, sys::write_addr};
 
 const CONTROL_MODULE_BASE: u32 = 0x44E10000;
 const CONF_GPMC_BEN1: u32 = 0x878; // GPIO1_28
@@ -8,6 +8,16 @@ const CONF_GMPC_A2: u32 = 0x848; // GPIO1_18
 const CONF_GMPC_A3: u32 = 0x84C; // GPIO1_19
 const CONF_UART1_CTSN: u32 = 0x978; // I2C2_SDA
 const CONF_UART1_RTSN: u32 = 0x97C; // I2C2_SCL
+const CONF_UART0_RXD: u32 = 0x970; // UART0_RXD
+const CONF_UART0_TXD: u32 = 0x974; // UART0_TXD
+const CONF_I2C0_SDA: u32 = 0x988; // I2C0_SDA
+const CONF_I2C0_SCL: u32 = 0x98C; // I2C0_SCL
+const CONF_SPI0_SCLK: u32 = 0x950; // EHRPWM0A
+const CONF_SPI0_D0: u32 = 0x954; // EHRPWM0B
+const CONF_SPI0_D1: u32 = 0x958; // SPI0_D1
+const CONF_SPI0_CS0: u32 = 0x95C; // SPI0_CS0
+const CONF_GPMC_AD8: u32 = 0x820; // EHRPWM2A
+const CONF_GPMC_AD9: u32 = 0x824; // EHRPWM2B
 
 pub fn configure() {
     set_pin_mode(CONF_GPMC_BEN1, 7, true, PullResistor::PullDown);
@@ -17,6 +27,34 @@ pub fn configure() {
     set_pin_mode(CONF_GMPC_A3, 7, true, PullResistor::PullDown);
     set_pin_mode(CONF_UART1_CTSN, 3, true, PullResistor::None);
     set_pin_mode(CONF_UART1_RTSN, 3, true, PullResistor::None);
+    set_pin_mode(CONF_UART0_RXD, 0, true, PullResistor::PullUp);
+    set_pin_mode(CONF_UART0_TXD, 0, false, PullResistor::PullDown);
+    set_pin_mode(CONF_I2C0_SDA, 0, true, PullResistor::None);
+    set_pin_mode(CONF_I2C0_SCL, 0, true, PullResistor::None);
+}
+
+// Routes the pin of a PWM output to its module. EHRPWM1 shares its pins
+// with GPIO1_18 and GPIO1_19, which can't be used as GPIOs afterwards.
+pub fn configure_pwm(channel: PwmChannel) {
+    let (offset, mode) = match channel {
+        PwmChannel::Ehrpwm0A => (CONF_SPI0_SCLK, 3),
+        PwmChannel::Ehrpwm0B => (CONF_SPI0_D0, 3),
+        PwmChannel::Ehrpwm1A => (CONF_GMPC_A2, 6),
+        PwmChannel::Ehrpwm1B => (CONF_GMPC_A3, 6),
+        PwmChannel::Ehrpwm2A => (CONF_GPMC_AD8, 4),
+        PwmChannel::Ehrpwm2B => (CONF_GPMC_AD9, 4),
+    };
+
+    set_pin_mode(offset, mode, false, PullResistor::None);
+}
+
+// Routes SPI0 to its pins, which takes the pins of EHRPWM0 away. The clock
+// pin needs its input enabled, as the module samples it.
+pub fn configure_spi() {
+    set_pin_mode(CONF_SPI0_SCLK, 0, true, PullResistor::None);
+    set_pin_mode(CONF_SPI0_D0, 0, true, PullResistor::PullUp);
+    set_pin_mode(CONF_SPI0_D1, 0, false, PullResistor::None);
+    set_pin_mode(CONF_SPI0_CS0, 0, false, PullResistor::PullUp);
 }
 
 pub fn set_pin_mode(offset: u32, mode: u32, input_enable: bool, pull_resistor: PullResistor) {
@@ -0,0 +1,8 @@
+use shared::kernel::Syscall;
+
+// Converts the voltage at the analog input into a 12 bit value. Reading a
+// channel above 7 terminates the task.
+pub fn read(channel: u8) -> u16 {
+    let syscall = Syscall::AdcRead { channel };
+    unsafe { syscall.call().unwrap().adc }
+}
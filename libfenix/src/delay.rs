@@ -0,0 +1,87 @@
+use core::arch::asm;
+
+use embedded_hal::delay::DelayNs;
+use shared::kernel::Syscall;
+
+use crate::sysclock::{micros, sleep};
+
+// Delay for driver crates running as tasks. Whole milliseconds are slept, so
+// other tasks can run in the meantime, only the remainder is busy waited in
+// the kernel.
+#[derive(Clone, Copy, Default)]
+pub struct Delay;
+
+impl Delay {
+    pub fn new() -> Self {
+        Delay
+    }
+}
+
+impl DelayNs for Delay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        if us >= 1000 {
+            sleep(us / 1000);
+        }
+
+        let micros = us % 1000;
+        if micros > 0 {
+            let syscall = Syscall::SleepMicros { micros };
+            syscall.call();
+        }
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        sleep(ms);
+    }
+}
+
+// Spins without yielding until `us` microseconds passed, e.g. for
+// bit-banging. The clock advances in steps of 31.25 us, so shorter delays
+// are rounded up to the next step.
+pub fn delay_us(us: u32) {
+    let start = micros();
+
+    // correct across a wrap of the clock
+    while micros().wrapping_sub(start) < us {
+        // added nop instruction to remove compiler optimizations
+        unsafe {
+            asm!("nop");
+        }
+    }
+}
+
+pub fn delay_ms(ms: u32) {
+    // split up, so the target stays below the wrap of the clock
+    for _ in 0..ms {
+        delay_us(1000);
+    }
+}
+
+// Delay for driver crates, which busy waits like `delay_us` instead of
+// sleeping
+#[derive(Clone, Copy, Default)]
+pub struct BusyDelay;
+
+impl BusyDelay {
+    pub fn new() -> Self {
+        BusyDelay
+    }
+}
+
+impl DelayNs for BusyDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        delay_us(us);
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        delay_ms(ms);
+    }
+}
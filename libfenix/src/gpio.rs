@@ -1,4 +1,39 @@
-use shared::{gpio::GpioPin, kernel::Syscall};
+use shared::{
+    gpio::{GpioBank, GpioPin},
+    kernel::{Syscall, NO_PIN_CHANGE},
+};
+
+pub use shared::gpio::{GpioEdge, GpioMode};
+
+blocking_syscall!(gpio_wait_edge, "0x24");
+
+// Blocks the task until the edge occurs on the input pin. Every task waiting
+// on the pin is woken by the same edge.
+pub fn wait_edge((pin, bank): GpioPin, edge: GpioEdge) {
+    // the pin number and the edge share a register
+    unsafe {
+        gpio_wait_edge(bank as u32, pin | ((edge as u32) << 8));
+    }
+}
+
+// Oldest edge recorded by the kernel on a pin with an enabled edge interrupt,
+// together with the level right after it. Edges are dropped while the
+// kernel's queue is full.
+pub fn next_change() -> Option<(GpioPin, bool)> {
+    let syscall = Syscall::GpioNextChange;
+    match unsafe { syscall.call().unwrap().pin_change } {
+        NO_PIN_CHANGE => None,
+        change => {
+            let bank = GpioBank::ALL[(change >> 8 & 0xFF) as usize];
+            Some(((change & 0xFF, bank), change >> 16 & 1 != 0))
+        }
+    }
+}
+
+pub fn set_mode(pin: GpioPin, mode: GpioMode) {
+    let syscall = Syscall::GpioMode { pin, mode };
+    syscall.call();
+}
 
 pub fn read(pin: GpioPin) -> bool {
     let syscall = Syscall::GpioRead { pin };
@@ -10,4 +45,20 @@ pub fn write(pin: GpioPin, value: bool) {
     syscall.call();
 }
 
+// Inverts the output in a single syscall, returns the new level
+pub fn toggle(pin: GpioPin) -> bool {
+    let syscall = Syscall::GpioToggle { pin };
+    unsafe { syscall.call().unwrap().gpio_toggled }
+}
+
+pub fn compare_swap(pin: GpioPin, expected: bool, new: bool) -> bool {
+    let syscall = Syscall::GpioCompareSwap { pin, expected, new };
+    unsafe { syscall.call().unwrap().gpio_swapped }
+}
+
+pub fn pulse(pin: GpioPin, micros: u32) {
+    let syscall = Syscall::GpioPulse { pin, micros };
+    syscall.call();
+}
+
 pub use shared::gpio::pins::*;
@@ -1,34 +1,78 @@
 use core::fmt::{self, Write};
 
 use shared::{
-    i2c::{I2cError, PRINT_ADDRESS}, kernel::Syscall
+    i2c::{I2cError, PRINT_ADDRESS, SCAN_ADDRESSES}, kernel::Syscall
 };
 
-pub fn write(address: u8, data: u8) -> I2cError {
+use crate::alloc::{vec, vec::Vec};
+
+pub fn write(address: u8, data: u8) -> Result<(), I2cError> {
     write_buf(address, &[data])
 }
 
-pub fn write_buf(address: u8, data: &[u8]) -> I2cError {
+// Fails with Nack if no device answers to the address
+pub fn write_buf(address: u8, data: &[u8]) -> Result<(), I2cError> {
     let syscall = Syscall::I2cWrite { address, data };
-    unsafe { syscall.call().unwrap().i2c_write }
+    unsafe { syscall.call().unwrap().i2c_write }.into_result()
 }
 
-pub fn write_str(address: u8, data: &str) -> I2cError {
+pub fn write_str(address: u8, data: &str) -> Result<(), I2cError> {
     write_buf(address, data.as_bytes())
 }
 
-pub fn write_char(address: u8, data: char) -> I2cError {
+pub fn write_char(address: u8, data: char) -> Result<(), I2cError> {
     write_buf(address, &[data as u8])
 }
 
+// Fills the whole buffer with bytes read from the device and returns how
+// many were read
+pub fn read(address: u8, buffer: &mut [u8]) -> Result<usize, I2cError> {
+    let len = buffer.len();
+
+    let syscall = Syscall::I2cRead { address, buffer };
+    match unsafe { syscall.call().unwrap().i2c_read } {
+        I2cError::Success => Ok(len),
+        error => Err(error),
+    }
+}
+
+// Addresses of all devices on the bus, which acknowledge their address
+pub fn scan() -> Vec<u8> {
+    let mut found = vec![0; SCAN_ADDRESSES.len()];
+
+    let syscall = Syscall::I2cScan { buffer: &mut found };
+    let len = unsafe { syscall.call().unwrap().i2c_found };
+    found.truncate(len as usize);
+
+    found
+}
+
+blocking_syscall!(wait_i2c_done, "0x1D");
+
+// Handle of a write started with `start_write`
+#[derive(Clone, Copy)]
+pub struct Transfer(u32);
+
+// Starts writing `data` in the background, so the task can keep computing.
+// Returns Busy if another transfer is still in flight.
+pub fn start_write(address: u8, data: &[u8]) -> Result<Transfer, I2cError> {
+    let syscall = Syscall::I2cStart { address, data };
+    match unsafe { syscall.call().unwrap().i2c_handle } {
+        0 => Err(I2cError::Busy),
+        handle => Ok(Transfer(handle)),
+    }
+}
+
+// Blocks until the transfer completed and returns its result
+pub fn wait(transfer: Transfer) -> Result<(), I2cError> {
+    I2cError::from(unsafe { wait_i2c_done(transfer.0, 0) }).into_result()
+}
+
 struct I2c {}
 
 impl Write for I2c {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        match write_str(PRINT_ADDRESS, s) {
-            I2cError::Success => Ok(()),
-            _ => Err(fmt::Error),
-        }
+        write_str(PRINT_ADDRESS, s).map_err(|_| fmt::Error)
     }
 }
 
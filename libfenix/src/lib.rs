@@ -1,22 +1,199 @@
 #![no_std]
 
+#[macro_use]
+mod syscall;
+
+pub mod adc;
 pub mod alloc;
+pub mod delay;
 pub mod gpio;
 pub mod i2c;
+pub mod pwm;
+pub mod spi;
+pub mod sync;
 mod sysclock;
+pub mod time;
+pub mod uart;
 
 pub use shared::kernel;
 pub use sysclock::*;
 
-use shared::kernel::Syscall;
+use core::fmt::{self, Write};
+
+use shared::collections::ArrayVec;
+use shared::kernel::{
+    MemInfo, PollTarget, SchedulerStatus, Syscall, TaskInfo, JOIN_EXITED, JOIN_OWN_TASK,
+    MAX_TASKS,
+};
+
+const MAX_AT_EXIT: usize = 8;
+const MAX_PANIC_MESSAGE: usize = 128;
+
+static mut AT_EXIT: [Option<fn()>; MAX_AT_EXIT] = [None; MAX_AT_EXIT];
+
+// Registers a callback which is run by `exit`, e.g. to release a resource the
+// task holds. Returns false if all slots are already taken.
+pub fn at_exit(callback: fn()) -> bool {
+    let callbacks = &raw mut AT_EXIT;
+
+    match unsafe { (*callbacks).iter_mut() }.find(|slot| slot.is_none()) {
+        Some(slot) => {
+            *slot = Some(callback);
+            true
+        }
+        None => false,
+    }
+}
+
+// Terminates the task, `code` is returned to tasks joining it
+pub fn exit(code: i32) {
+    let callbacks = &raw mut AT_EXIT;
+
+    // run in reverse order of registration
+    for callback in unsafe { (*callbacks).iter_mut() }.rev() {
+        if let Some(callback) = callback.take() {
+            callback();
+        }
+    }
+
+    let syscall = Syscall::Exit { code };
+    syscall.call();
+}
+
+// Names the task, names longer than 16 bytes are truncated
+pub fn set_name(name: &str) {
+    let syscall = Syscall::SetName {
+        name: name.as_bytes(),
+    };
+    syscall.call();
+}
+
+// Bytes left on the task's heap before allocations start failing
+pub fn heap_remaining() -> usize {
+    let syscall = Syscall::TaskHeapRemaining;
+    unsafe { syscall.call().unwrap().heap_remaining }
+}
+
+// Memory usage of the whole system, not just of this task
+pub fn mem_info() -> MemInfo {
+    let mut info = MemInfo::default();
+
+    let syscall = Syscall::MemInfo { info: &mut info };
+    syscall.call();
+
+    info
+}
+
+// Tells whether the system was last idle because every task waits or
+// because none is left
+pub fn scheduler_status() -> SchedulerStatus {
+    let syscall = Syscall::SchedulerStatus;
+    unsafe { syscall.call().unwrap().scheduler_status }
+}
+
+// Checks whether a resource is ready without blocking the task
+pub fn poll(what: PollTarget) -> bool {
+    let syscall = Syscall::Poll { what };
+    unsafe { syscall.call().unwrap().ready }
+}
 
-pub fn exit() {
-    let syscall = Syscall::Exit;
+// Prints the page mappings of the task, only does something in debug builds
+// of the kernel
+pub fn dump_page_table() {
+    let syscall = Syscall::DumpPageTable;
     syscall.call();
 }
 
+// Id of the calling task, which `wake` and `spawn` refer to tasks by
+pub fn task_id() -> usize {
+    let syscall = Syscall::TaskId;
+    unsafe { syscall.call().unwrap().task_id as usize }
+}
+
+// Resets the watchdog, which restarts the board if the kernel enabled it and
+// no task kicks it within its timeout
+pub fn kick_watchdog() {
+    let syscall = Syscall::WatchdogKick;
+    syscall.call();
+}
+
+blocking_syscall!(join_task, "0x27", u64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinError {
+    // a task can't wait for its own termination
+    OwnTask,
+}
+
+// Id, state, priority and name of every task which hasn't terminated
+pub fn task_info() -> ArrayVec<TaskInfo, MAX_TASKS> {
+    let mut tasks = [TaskInfo::default(); MAX_TASKS];
+
+    let syscall = Syscall::TaskInfo { tasks: &mut tasks };
+    let count = unsafe { syscall.call().unwrap().tasks } as usize;
+
+    let mut info = ArrayVec::new();
+    for task in tasks.iter().take(count) {
+        let _ = info.push(*task);
+    }
+
+    info
+}
+
+// Terminates another task, for the own id it exits without running the
+// `at_exit` callbacks. Returns false if there is no such task or it already
+// terminated.
+pub fn kill(id: usize) -> bool {
+    let syscall = Syscall::Kill { id };
+    unsafe { syscall.call().unwrap().killed }
+}
+
+// Blocks until the task terminated and returns its exit code, which is None
+// if the task didn't exit by itself or doesn't exist. Returns right away if
+// the task already terminated.
+pub fn join(id: usize) -> Result<Option<i32>, JoinError> {
+    let result = unsafe { join_task(id as u32, 0) };
+    let code = (result >> 32) as i32;
+
+    match result as u32 {
+        JOIN_OWN_TASK => Err(JoinError::OwnTask),
+        JOIN_EXITED => Ok(Some(code)),
+        _ => Ok(None),
+    }
+}
+
+// Starts a new task running the program `code`, which is copied, so the
+// buffer can be reused right away. Returns the id of the new task.
+pub fn spawn(code: &[u8]) -> Option<usize> {
+    let syscall = Syscall::Spawn { code };
+    match unsafe { syscall.call().unwrap().spawned } {
+        u32::MAX => None,
+        id => Some(id as usize),
+    }
+}
+
+// Collects the panic message on the stack, as the heap may be what failed.
+// Longer messages are cut off.
+struct PanicMessage(ArrayVec<u8, MAX_PANIC_MESSAGE>);
+
+impl Write for PanicMessage {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if self.0.push(byte).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[panic_handler]
-fn panic(_info: &core::panic::PanicInfo) -> ! {
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    let mut message = PanicMessage(ArrayVec::new());
+    let _ = writeln!(message, "{}", info);
+    uart::write(message.0.as_slice());
+
     let syscall = Syscall::Panic;
     syscall.call();
 
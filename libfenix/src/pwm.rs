@@ -0,0 +1,14 @@
+use shared::kernel::Syscall;
+
+pub use shared::pwm::PwmChannel;
+
+// Sets the share of the period in which the output is high, values above
+// 100 are treated as 100. The first call sets up the module with a period
+// of 1 ms.
+pub fn set_duty(channel: PwmChannel, percent: u8) {
+    let syscall = Syscall::PwmDuty {
+        channel,
+        duty: percent,
+    };
+    syscall.call();
+}
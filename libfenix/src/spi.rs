@@ -0,0 +1,8 @@
+use shared::kernel::Syscall;
+
+// Sends every byte of the buffer over SPI0 and replaces it with the byte
+// received at the same time. The bus runs in mode 0 at 1 MHz.
+pub fn transfer(buffer: &mut [u8]) {
+    let syscall = Syscall::SpiTransfer { buffer };
+    syscall.call();
+}
@@ -0,0 +1,158 @@
+use shared::kernel::{Syscall, BARRIER_INVALID, MUTEX_HELD, MUTEX_INVALID, SEMAPHORE_INVALID};
+
+blocking_syscall!(barrier_wait, "0x13");
+blocking_syscall!(mutex_lock, "0x2A");
+blocking_syscall!(semaphore_wait, "0x2D");
+
+// Blocks tasks calling `wait` until `parties` tasks arrived, then releases
+// all of them together. The barrier can be reused for the next phase.
+#[derive(Clone, Copy)]
+pub struct Barrier {
+    id: u32,
+}
+
+impl Barrier {
+    // Returns None if all barriers of the kernel are taken
+    pub fn new(parties: u32) -> Option<Self> {
+        let syscall = Syscall::BarrierCreate { parties };
+        match unsafe { syscall.call().unwrap().barrier } {
+            u32::MAX => None,
+            id => Some(Barrier { id }),
+        }
+    }
+
+    // Returns true for exactly one task, the last one to arrive
+    pub fn wait(&self) -> Result<bool, InvalidBarrier> {
+        match unsafe { barrier_wait(self.id, 0) } {
+            BARRIER_INVALID => Err(InvalidBarrier),
+            leader => Ok(leader == 1),
+        }
+    }
+
+    // Frees the barrier, tasks waiting at it get InvalidBarrier. Only the
+    // task which created it can destroy it, it's destroyed with the task
+    // otherwise.
+    pub fn destroy(self) -> bool {
+        let syscall = Syscall::BarrierDestroy {
+            id: self.id as usize,
+        };
+        unsafe { syscall.call().unwrap().destroyed }
+    }
+}
+
+// The barrier doesn't exist or was destroyed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidBarrier;
+
+// Gives one task at a time access to a resource shared with other tasks,
+// e.g. a bus. A task locking it while another task holds it is blocked until
+// the mutex is handed over to it. Data shared with interrupt handlers is
+// guarded by shared::interrupts::CriticalSection instead, which masks
+// interrupts.
+#[derive(Clone, Copy)]
+pub struct Mutex {
+    id: u32,
+}
+
+impl Mutex {
+    // Returns None if all mutexes of the kernel are taken
+    pub fn new() -> Option<Self> {
+        let syscall = Syscall::MutexCreate;
+        match unsafe { syscall.call().unwrap().mutex } {
+            u32::MAX => None,
+            id => Some(Mutex { id }),
+        }
+    }
+
+    pub fn lock(&self) -> Result<MutexGuard<'_>, LockError> {
+        match unsafe { mutex_lock(self.id, 0) } {
+            MUTEX_HELD => Err(LockError::AlreadyHeld),
+            MUTEX_INVALID => Err(LockError::Invalid),
+            _ => Ok(MutexGuard { mutex: self }),
+        }
+    }
+
+    // Frees the mutex, tasks waiting for it get LockError::Invalid. Only the
+    // task which created it can destroy it, it's destroyed with the task
+    // otherwise.
+    pub fn destroy(self) -> bool {
+        let syscall = Syscall::MutexDestroy {
+            id: self.id as usize,
+        };
+        unsafe { syscall.call().unwrap().destroyed }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockError {
+    // the task already holds the mutex, it would wait for itself forever
+    AlreadyHeld,
+    // the mutex doesn't exist or was destroyed
+    Invalid,
+}
+
+// Unlocks the mutex when dropped
+pub struct MutexGuard<'a> {
+    mutex: &'a Mutex,
+}
+
+impl Drop for MutexGuard<'_> {
+    fn drop(&mut self) {
+        let syscall = Syscall::MutexUnlock {
+            id: self.mutex.id as usize,
+        };
+        syscall.call();
+    }
+}
+
+// Counts units shared between tasks, e.g. filled slots between a producer
+// and a consumer. `wait` blocks while no unit is left.
+#[derive(Clone, Copy)]
+pub struct Semaphore {
+    id: u32,
+}
+
+impl Semaphore {
+    // Returns None if all semaphores of the kernel are taken
+    pub fn new(count: u32) -> Option<Self> {
+        let syscall = Syscall::SemaphoreCreate { count };
+        match unsafe { syscall.call().unwrap().semaphore } {
+            u32::MAX => None,
+            id => Some(Semaphore { id }),
+        }
+    }
+
+    // Takes a unit, blocks until another task signals if none is left
+    pub fn wait(&self) -> Result<(), InvalidSemaphore> {
+        match unsafe { semaphore_wait(self.id, 0) } {
+            SEMAPHORE_INVALID => Err(InvalidSemaphore),
+            _ => Ok(()),
+        }
+    }
+
+    // Returns a unit, which wakes one waiting task
+    pub fn signal(&self) -> Result<(), InvalidSemaphore> {
+        let syscall = Syscall::SemaphoreSignal {
+            id: self.id as usize,
+        };
+        if unsafe { syscall.call().unwrap().signaled } {
+            Ok(())
+        } else {
+            Err(InvalidSemaphore)
+        }
+    }
+
+    // Frees the semaphore, tasks waiting for it get InvalidSemaphore. Only the
+    // task which created it can destroy it, it's destroyed with the task
+    // otherwise.
+    pub fn destroy(self) -> bool {
+        let syscall = Syscall::SemaphoreDestroy {
+            id: self.id as usize,
+        };
+        unsafe { syscall.call().unwrap().destroyed }
+    }
+}
+
+// The semaphore doesn't exist or was destroyed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidSemaphore;
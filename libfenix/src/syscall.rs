@@ -0,0 +1,32 @@
+// Generates `$name(arg0, arg1) -> u32`, or the given return type, issuing a
+// syscall which may block the calling task. The registers and cpsr are stacked in the layout the kernel
+// restores tasks from, with the stack pointer and return address passed in
+// r0/r1 and the arguments in r2/r3, so the kernel is able to park and later resume the task.
+// If the kernel doesn't block, the syscall returns normally and the stacked
+// registers are discarded again.
+macro_rules! blocking_syscall {
+    ($name:ident, $number:literal) => {
+        blocking_syscall!($name, $number, u32);
+    };
+    // a u64 is returned in r0 and r1, for syscalls returning two values
+    ($name:ident, $number:literal, $ret:ty) => {
+        core::arch::global_asm!(
+            concat!(".global ", stringify!($name)),
+            concat!(stringify!($name), ":"),
+            "stmfd sp!, {{r0-r12, lr}}",
+            "mov r2, r0",
+            "mov r3, r1",
+            "mrs r0, cpsr",
+            "push {{r0}}",
+            "mov r0, sp",
+            "mov r1, lr",
+            concat!("svc #", $number),
+            "add sp, sp, #8",
+            "ldmfd sp!, {{r1-r12, pc}}",
+        );
+
+        extern "C" {
+            fn $name(arg0: u32, arg1: u32) -> $ret;
+        }
+    };
+}
@@ -1,4 +1,3 @@
-use core::arch::global_asm;
 use shared::kernel::Syscall;
 
 pub fn millis() -> u32 {
@@ -6,30 +5,68 @@ pub fn millis() -> u32 {
     unsafe { syscall.call().unwrap().millis }
 }
 
-global_asm!(
-    "
-    yield_task:
-        stmfd sp!, {{r0-r12, lr}}
+// Microseconds since boot, which wrap after about 71 minutes like `millis`
+// wraps after 49 days. The clock advances in steps of 31.25 us, the period of
+// the 32 kHz system timer.
+pub fn micros() -> u32 {
+    let syscall = Syscall::Micros;
+    unsafe { syscall.call().unwrap().micros }
+}
+
+// Microseconds since boot, wide enough to never wrap
+pub fn micros64() -> u64 {
+    let mut micros = 0;
 
-        mov r2, r0
+    let syscall = Syscall::Time64 {
+        micros: &mut micros,
+    };
+    syscall.call();
 
-        mrs r0, cpsr
-        push {{r0}}
+    micros
+}
 
-        mov r0, sp
-        mov r1, lr
-        mov r2, r2
-        svc #0x1
-"
-);
+blocking_syscall!(sleep_task, "0x16");
 
+// Blocks the task for at least `ms` milliseconds
 pub fn sleep(ms: u32) {
-    let until = millis() + ms;
     unsafe {
-        yield_task(until);
+        sleep_task(ms, 0);
+    }
+}
+
+// Sets how many milliseconds the task may run before being preempted, 0
+// restores the default
+pub fn set_quantum(ms: u32) {
+    let syscall = Syscall::SetQuantum { millis: ms };
+    syscall.call();
+}
+
+blocking_syscall!(yield_task, "0x1");
+blocking_syscall!(sleep_cancelable_task, "0xA");
+blocking_syscall!(reschedule_task, "0x15");
+
+// Hands the cpu to the next task, the task runs again on its next turn even
+// if no other task is runnable
+pub fn yield_now() {
+    unsafe {
+        yield_task(0, 0);
     }
 }
 
-extern "C" {
-    fn yield_task(ms: u32);
+// Sleeps like `sleep`, but another task can end the sleep early using `wake`.
+// Returns true if the task was woken before the time elapsed.
+pub fn sleep_cancelable(ms: u32) -> bool {
+    let until = millis() + ms;
+    unsafe { sleep_cancelable_task(until, 0) != 0 }
+}
+
+// Lets another runnable task run, but returns right away if there is none.
+// Returns true if the task was switched out.
+pub fn reschedule() -> bool {
+    unsafe { reschedule_task(0, 0) != 0 }
+}
+
+pub fn wake(id: usize) -> bool {
+    let syscall = Syscall::Wake { id };
+    unsafe { syscall.call().unwrap().woken }
 }
@@ -0,0 +1,71 @@
+use core::ops::Add;
+
+use crate::millis;
+
+// Span of time with millisecond resolution
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Duration {
+    millis: u32,
+}
+
+impl Duration {
+    pub const ZERO: Duration = Duration { millis: 0 };
+
+    pub const fn from_millis(millis: u32) -> Self {
+        Duration { millis }
+    }
+
+    pub const fn from_secs(secs: u32) -> Self {
+        Duration {
+            millis: secs.saturating_mul(1000),
+        }
+    }
+
+    pub const fn as_millis(&self) -> u32 {
+        self.millis
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration::from_millis(self.millis.saturating_add(rhs.millis))
+    }
+}
+
+// Point in time read from the millisecond clock. The clock wraps after about
+// 49 days, so only instants less than that apart can be compared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Instant {
+    millis: u32,
+}
+
+impl Instant {
+    pub fn now() -> Self {
+        Instant { millis: millis() }
+    }
+
+    // Time passed since the instant, correct across a wrap of the clock
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        Duration::from_millis(self.millis.wrapping_sub(earlier.millis))
+    }
+
+    pub fn has_elapsed(&self, duration: Duration) -> bool {
+        self.elapsed() >= duration
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, rhs: Duration) -> Instant {
+        Instant {
+            millis: self.millis.wrapping_add(rhs.millis),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use core::fmt::{self, Write};
+
+use shared::kernel::Syscall;
+
+pub fn write(data: &[u8]) {
+    let syscall = Syscall::Print { data };
+    syscall.call();
+}
+
+pub fn write_str(data: &str) {
+    write(data.as_bytes())
+}
+
+struct Uart {}
+
+impl Write for Uart {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_str(s);
+        Ok(())
+    }
+}
+
+// Logs over the kernel's UART, which needs no device listening on the I2C bus
+pub fn print(args: fmt::Arguments<'_>) {
+    let mut uart = Uart {};
+    uart.write_fmt(args).unwrap();
+}
+
+pub fn println(args: fmt::Arguments<'_>) {
+    let mut uart = Uart {};
+    uart.write_fmt(format_args!("{}\n", args)).unwrap();
+}
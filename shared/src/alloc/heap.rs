@@ -1,11 +1,27 @@
-use core::{alloc::GlobalAlloc, ptr};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    mem, ptr,
+};
 
 use crate::interrupts::CriticalSection;
 
 pub struct BumpAllocator {
     heap_start: usize,
     heap_end: usize,
-    next: CriticalSection<usize>,
+    state: CriticalSection<HeapState>,
+}
+
+struct HeapState {
+    next: usize,
+    // address of the first freed block, 0 if no block was freed
+    free: usize,
+}
+
+// Header written into every freed block, linking it to the next one
+#[repr(C)]
+struct FreeBlock {
+    size: usize,
+    next: usize,
 }
 
 impl BumpAllocator {
@@ -13,7 +29,7 @@ impl BumpAllocator {
         Self {
             heap_start: 0,
             heap_end: 0,
-            next: CriticalSection::new(0),
+            state: CriticalSection::new(HeapState { next: 0, free: 0 }),
         }
     }
 
@@ -21,14 +37,96 @@ impl BumpAllocator {
         Self {
             heap_start: start,
             heap_end: 0,
-            next: CriticalSection::new(start),
+            state: CriticalSection::new(HeapState {
+                next: start,
+                free: 0,
+            }),
         }
     }
 
     pub fn init(&mut self, start: usize, end: usize) {
         self.heap_start = start;
         self.heap_end = end;
-        *self.next.lock() = start;
+
+        let mut state = self.state.lock();
+        state.next = start;
+        state.free = 0;
+    }
+
+    pub fn used(&self) -> usize {
+        self.state.lock().next.saturating_sub(self.heap_start)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.heap_end.saturating_sub(self.state.lock().next)
+    }
+}
+
+impl BumpAllocator {
+    // Walks the free list until `matches` accepts a block and returns it with
+    // the address of its predecessor. The list lives in memory a task can
+    // write to, so a corrupted entry or a cycle cuts the list off there
+    unsafe fn find_free(
+        &self,
+        state: &mut HeapState,
+        mut matches: impl FnMut(usize, &FreeBlock) -> bool,
+    ) -> Option<(usize, usize, FreeBlock)> {
+        // every entry takes at least one header, so a longer walk is a cycle
+        let max_blocks = (state.next - self.heap_start) / mem::size_of::<FreeBlock>();
+
+        let mut previous: usize = 0;
+        let mut current = state.free;
+        for _ in 0..max_blocks {
+            if current == 0 {
+                return None;
+            }
+
+            let Some(block) = self.free_block(state, current) else {
+                break;
+            };
+
+            if matches(current, &block) {
+                return Some((previous, current, block));
+            }
+
+            previous = current;
+            current = block.next;
+        }
+
+        if current != 0 {
+            self.set_next(state, previous, 0);
+        }
+        None
+    }
+
+    // Reads the free list entry at `address` if it lies within the handed out
+    // part of the heap
+    unsafe fn free_block(&self, state: &HeapState, address: usize) -> Option<FreeBlock> {
+        let header_end = address.checked_add(mem::size_of::<FreeBlock>())?;
+        if address < self.heap_start
+            || header_end > state.next
+            || !address.is_multiple_of(mem::align_of::<FreeBlock>())
+        {
+            return None;
+        }
+
+        let block = (address as *const FreeBlock).read();
+        let end = address.checked_add(block.size)?;
+        if block.size < mem::size_of::<FreeBlock>()
+            || !block.size.is_multiple_of(mem::align_of::<FreeBlock>())
+            || end > state.next
+        {
+            return None;
+        }
+
+        Some(block)
+    }
+
+    unsafe fn set_next(&self, state: &mut HeapState, previous: usize, next: usize) {
+        match previous {
+            0 => state.free = next,
+            previous => (*(previous as *mut FreeBlock)).next = next,
+        }
     }
 }
 
@@ -39,21 +137,76 @@ impl Default for BumpAllocator {
 }
 
 unsafe impl GlobalAlloc for BumpAllocator {
-    unsafe fn alloc(&self, layout: core::alloc::Layout) -> *mut u8 {
-        let mut current = self.next.lock();
-        let alloc_start = align_up(*current, layout.align());
-        let alloc_end = alloc_start.saturating_add(layout.size());
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let size = block_size(layout);
+        let mut state = self.state.lock();
+
+        // reuse a freed block of the same size first
+        let reusable = self.find_free(&mut state, |address, block| {
+            block.size == size && address.is_multiple_of(layout.align())
+        });
+        if let Some((previous, current, block)) = reusable {
+            self.set_next(&mut state, previous, block.next);
+            return current as *mut u8;
+        }
+
+        let alloc_start = align_up(state.next, layout.align().max(mem::align_of::<FreeBlock>()));
+        let alloc_end = alloc_start.saturating_add(size);
 
         if alloc_end > self.heap_end {
             ptr::null_mut()
         } else {
-            *current = alloc_end;
+            state.next = alloc_end;
             alloc_start as *mut u8
         }
     }
 
-    // Not used until now
-    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: core::alloc::Layout) {}
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let size = block_size(layout);
+        let address = ptr as usize;
+        let mut state = self.state.lock();
+
+        // the pointer may come from a task, so only memory which was handed
+        // out before is linked into the free list
+        let end = address.checked_add(size);
+        if address < self.heap_start
+            || end.is_none_or(|end| end > state.next)
+            || !address.is_multiple_of(layout.align().max(mem::align_of::<FreeBlock>()))
+        {
+            return;
+        }
+
+        // a block which is already on the free list would link to itself
+        if self
+            .find_free(&mut state, |current, block| {
+                address < current + block.size && current < address + size
+            })
+            .is_some()
+        {
+            return;
+        }
+
+        // the most recent allocation is simply given back to the bump region
+        if address + size == state.next {
+            state.next = address;
+            return;
+        }
+
+        let block = ptr as *mut FreeBlock;
+        block.write(FreeBlock {
+            size,
+            next: state.free,
+        });
+        state.free = address;
+    }
+}
+
+// Every block is large enough to hold the free list header once it's freed
+fn block_size(layout: Layout) -> usize {
+    align_up(
+        layout.size().max(mem::size_of::<FreeBlock>()),
+        mem::align_of::<FreeBlock>(),
+    )
 }
 
 fn align_up(addr: usize, align: usize) -> usize {
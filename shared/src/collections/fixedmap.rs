@@ -0,0 +1,157 @@
+use core::hash::{Hash, Hasher};
+
+// Open addressing hash map with a fixed number of slots, which never
+// allocates. Removed entries leave a tombstone behind so probing continues
+// past them, the slot is reused by the next insert.
+pub struct FixedMap<K: PartialEq + Copy + Hash, V, const N: usize> {
+    slots: [Slot<K, V>; N],
+    len: usize,
+}
+
+enum Slot<K, V> {
+    Empty,
+    Deleted,
+    Occupied(K, V),
+}
+
+impl<K: PartialEq + Copy + Hash, V, const N: usize> FixedMap<K, V, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { Slot::Empty }; N],
+            len: 0,
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns the previous value of the key, or gives the entry back if the
+    // map is full
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let mut free = None;
+
+        for index in self.probe(key) {
+            match &mut self.slots[index] {
+                Slot::Occupied(k, v) if *k == key => {
+                    return Ok(Some(core::mem::replace(v, value)));
+                }
+                Slot::Occupied(..) => {}
+                Slot::Deleted => {
+                    free.get_or_insert(index);
+                }
+                Slot::Empty => {
+                    free.get_or_insert(index);
+                    break;
+                }
+            }
+        }
+
+        match free {
+            Some(index) => {
+                self.slots[index] = Slot::Occupied(key, value);
+                self.len += 1;
+
+                Ok(None)
+            }
+            None => Err((key, value)),
+        }
+    }
+
+    pub fn get(&self, key: K) -> Option<&V> {
+        match &self.slots[self.find(key)?] {
+            Slot::Occupied(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        let index = self.find(key)?;
+
+        match &mut self.slots[index] {
+            Slot::Occupied(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.find(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<V> {
+        let index = self.find(key)?;
+
+        match core::mem::replace(&mut self.slots[index], Slot::Deleted) {
+            Slot::Occupied(_, value) => {
+                self.len -= 1;
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Occupied(key, value) => Some((*key, value)),
+            _ => None,
+        })
+    }
+
+    fn find(&self, key: K) -> Option<usize> {
+        for index in self.probe(key) {
+            match &self.slots[index] {
+                Slot::Occupied(k, _) if *k == key => return Some(index),
+                Slot::Empty => return None,
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    // linear probing, starting at the slot the key hashes to
+    fn probe(&self, key: K) -> impl Iterator<Item = usize> {
+        let mut hasher = FnvHasher::new();
+        key.hash(&mut hasher);
+        let start = hasher.finish() as usize;
+
+        (0..N).map(move |offset| start.wrapping_add(offset) % N)
+    }
+}
+
+impl<K: PartialEq + Copy + Hash, V, const N: usize> Default for FixedMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct FnvHasher(u32);
+
+impl FnvHasher {
+    const OFFSET_BASIS: u32 = 0x811C_9DC5;
+    const PRIME: u32 = 0x0100_0193;
+
+    fn new() -> Self {
+        FnvHasher(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u32).wrapping_mul(Self::PRIME);
+        }
+    }
+}
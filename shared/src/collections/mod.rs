@@ -0,0 +1,5 @@
+pub mod arrayvec;
+pub mod fixedmap;
+
+pub use arrayvec::ArrayVec;
+pub use fixedmap::FixedMap;
@@ -1,6 +1,7 @@
 pub type GpioPin = (u32, GpioBank);
 
 #[repr(u32)]
+#[derive(Clone, Copy)]
 pub enum GpioBank {
     Gpio0 = 0x44E0_7000,
     Gpio1 = 0x4804_C000,
@@ -8,14 +9,75 @@ pub enum GpioBank {
     Gpio3 = 0x481A_E000,
 }
 
-impl From<u32> for GpioBank {
-    fn from(value: u32) -> Self {
+impl GpioBank {
+    pub const ALL: [GpioBank; 4] = [
+        GpioBank::Gpio0,
+        GpioBank::Gpio1,
+        GpioBank::Gpio2,
+        GpioBank::Gpio3,
+    ];
+
+    pub fn index(&self) -> usize {
+        match self {
+            GpioBank::Gpio0 => 0,
+            GpioBank::Gpio1 => 1,
+            GpioBank::Gpio2 => 2,
+            GpioBank::Gpio3 => 3,
+        }
+    }
+}
+
+impl TryFrom<u32> for GpioBank {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0x44E0_7000 => Ok(GpioBank::Gpio0),
+            0x4804_C000 => Ok(GpioBank::Gpio1),
+            0x481A_C000 => Ok(GpioBank::Gpio2),
+            0x481A_E000 => Ok(GpioBank::Gpio3),
+            _ => Err(()),
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum GpioMode {
+    Input = 0,
+    Output = 1,
+}
+
+impl TryFrom<u32> for GpioMode {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(GpioMode::Input),
+            1 => Ok(GpioMode::Output),
+            _ => Err(()),
+        }
+    }
+}
+
+// Edges of the input, which raise an interrupt
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum GpioEdge {
+    Rising = 0,
+    Falling = 1,
+    Change = 2,
+}
+
+impl TryFrom<u32> for GpioEdge {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
         match value {
-            0x44E0_7000 => GpioBank::Gpio0,
-            0x4804_C000 => GpioBank::Gpio1,
-            0x481A_C000 => GpioBank::Gpio2,
-            0x481A_E000 => GpioBank::Gpio3,
-            _ => panic!("invalid gpio bank"),
+            0 => Ok(GpioEdge::Rising),
+            1 => Ok(GpioEdge::Falling),
+            2 => Ok(GpioEdge::Change),
+            _ => Err(()),
         }
     }
 }
@@ -1,13 +1,48 @@
+use core::fmt;
+
 use embedded_hal::i2c;
 
 pub const PRINT_ADDRESS: u8 = 0x10;
 
+// 7-bit addresses probed by a bus scan, the others are reserved
+pub const SCAN_ADDRESSES: core::ops::RangeInclusive<u8> = 0x08..=0x77;
+
 #[derive(Clone, Copy, Debug)]
 #[repr(u32)]
 pub enum I2cError {
     Success = 0,
     Nack = 1,
     ArbitrationLoss = 2,
+    Overrun = 3,
+    // another transfer is still in flight
+    Busy = 4,
+    // the handle of a transfer is unknown or its result was already replaced
+    UnknownTransfer = 5,
+}
+
+impl I2cError {
+    // Success is the only value which isn't an error
+    pub fn into_result(self) -> Result<(), I2cError> {
+        match self {
+            I2cError::Success => Ok(()),
+            error => Err(error),
+        }
+    }
+}
+
+impl fmt::Display for I2cError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            I2cError::Success => "success",
+            I2cError::Nack => "no acknowledge",
+            I2cError::ArbitrationLoss => "arbitration lost",
+            I2cError::Overrun => "overrun",
+            I2cError::Busy => "bus busy",
+            I2cError::UnknownTransfer => "unknown transfer",
+        };
+
+        f.write_str(message)
+    }
 }
 
 impl i2c::Error for I2cError {
@@ -15,7 +50,9 @@ impl i2c::Error for I2cError {
         match self {
             I2cError::Nack => i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Unknown),
             I2cError::ArbitrationLoss => i2c::ErrorKind::ArbitrationLoss,
-            I2cError::Success => i2c::ErrorKind::Other,
+            I2cError::Overrun => i2c::ErrorKind::Overrun,
+            I2cError::Busy => i2c::ErrorKind::Bus,
+            I2cError::Success | I2cError::UnknownTransfer => i2c::ErrorKind::Other,
         }
     }
 }
@@ -26,6 +63,9 @@ impl From<u32> for I2cError {
             0 => I2cError::Success,
             1 => I2cError::Nack,
             2 => I2cError::ArbitrationLoss,
+            3 => I2cError::Overrun,
+            4 => I2cError::Busy,
+            5 => I2cError::UnknownTransfer,
             _ => I2cError::Success,
         }
     }
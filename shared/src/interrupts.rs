@@ -1,7 +1,17 @@
-use core::{arch::asm, cell::UnsafeCell, ops::{Deref, DerefMut}};
-
+use core::{
+    arch::asm,
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Masks interrupts while locked, which guards data shared between a task and
+// interrupt handlers. Tasks which have to wait for each other use the mutexes
+// of the kernel instead.
 pub struct CriticalSection<T: Sized> {
     inner: UnsafeCell<T>,
+    // set while a guard exists, so `try_lock` can back off
+    locked: AtomicBool,
 }
 
 unsafe impl<T: Sized + Sync> Sync for CriticalSection<T> {}
@@ -10,18 +20,42 @@ impl<T> CriticalSection<T> {
     pub const fn new(value: T) -> Self {
         Self {
             inner: UnsafeCell::new(value),
+            locked: AtomicBool::new(false),
         }
     }
 
     pub fn lock(&self) -> CriticalSectionGuard<T> {
         let cpsr = disable_interrupts();
-        CriticalSectionGuard { mutex: self, cpsr }
+        let was_locked = self.locked.swap(true, Ordering::Relaxed);
+        CriticalSectionGuard {
+            mutex: self,
+            cpsr,
+            was_locked,
+        }
+    }
+
+    // Returns None if the value is already locked, e.g. by a task which an
+    // interrupt handler preempted while it had interrupts enabled again
+    pub fn try_lock(&self) -> Option<CriticalSectionGuard<'_, T>> {
+        let cpsr = disable_interrupts();
+        if self.locked.swap(true, Ordering::Relaxed) {
+            restore_cpsr(cpsr);
+            return None;
+        }
+
+        Some(CriticalSectionGuard {
+            mutex: self,
+            cpsr,
+            was_locked: false,
+        })
     }
 }
 
 pub struct CriticalSectionGuard<'a, T: Sized> {
     mutex: &'a CriticalSection<T>,
     cpsr: u32,
+    // nested guards leave the section locked for the outer guard
+    was_locked: bool,
 }
 
 impl<T> Deref for CriticalSectionGuard<'_, T> {
@@ -40,6 +74,7 @@ impl<T> DerefMut for CriticalSectionGuard<'_, T> {
 
 impl<T> Drop for CriticalSectionGuard<'_, T> {
     fn drop(&mut self) {
+        self.mutex.locked.store(self.was_locked, Ordering::Relaxed);
         restore_cpsr(self.cpsr);
     }
 }
@@ -1,8 +1,14 @@
-use crate::{gpio::GpioPin, i2c::I2cError};
+use crate::{
+    gpio::{GpioEdge, GpioMode, GpioPin},
+    i2c::I2cError,
+    pwm::PwmChannel,
+};
 use core::{alloc::Layout, arch::asm};
 
 pub enum Syscall<'a> {
-    Exit,
+    Exit {
+        code: i32,
+    },
     Yield {
         sp: u32,
         pc: u32,
@@ -28,13 +34,267 @@ pub enum Syscall<'a> {
         ptr: *mut u8,
         layout: Layout,
     },
+    GpioPulse {
+        pin: GpioPin,
+        micros: u32,
+    },
+    SleepCancelable {
+        sp: u32,
+        pc: u32,
+        until: u32,
+    },
+    Wake {
+        id: usize,
+    },
+    GpioCompareSwap {
+        pin: GpioPin,
+        expected: bool,
+        new: bool,
+    },
+    SetQuantum {
+        millis: u32,
+    },
+    TaskHeapRemaining,
+    Poll {
+        what: PollTarget,
+    },
+    DumpPageTable,
+    MemInfo {
+        info: &'a mut MemInfo,
+    },
+    BarrierCreate {
+        parties: u32,
+    },
+    BarrierWait {
+        sp: u32,
+        pc: u32,
+        id: usize,
+    },
+    SleepMicros {
+        micros: u32,
+    },
+    Reschedule {
+        sp: u32,
+        pc: u32,
+    },
+    Sleep {
+        sp: u32,
+        pc: u32,
+        millis: u32,
+    },
+    SchedulerStatus,
+    I2cRead {
+        address: u8,
+        buffer: &'a mut [u8],
+    },
+    SetName {
+        name: &'a [u8],
+    },
+    GpioToggle {
+        pin: GpioPin,
+    },
+    GpioMode {
+        pin: GpioPin,
+        mode: GpioMode,
+    },
+    I2cStart {
+        address: u8,
+        data: &'a [u8],
+    },
+    WaitI2cDone {
+        sp: u32,
+        pc: u32,
+        handle: u32,
+    },
+    Time64 {
+        micros: &'a mut u64,
+    },
+    Print {
+        data: &'a [u8],
+    },
+    Spawn {
+        code: &'a [u8],
+    },
+    TaskId,
+    PwmDuty {
+        channel: PwmChannel,
+        duty: u8,
+    },
+    AdcRead {
+        channel: u8,
+    },
+    GpioWaitEdge {
+        sp: u32,
+        pc: u32,
+        pin: GpioPin,
+        edge: GpioEdge,
+    },
+    SpiTransfer {
+        buffer: &'a mut [u8],
+    },
+    WatchdogKick,
+    Join {
+        sp: u32,
+        pc: u32,
+        id: usize,
+    },
+    I2cScan {
+        buffer: &'a mut [u8],
+    },
+    MutexCreate,
+    MutexLock {
+        sp: u32,
+        pc: u32,
+        id: usize,
+    },
+    MutexUnlock {
+        id: usize,
+    },
+    SemaphoreCreate {
+        count: u32,
+    },
+    SemaphoreWait {
+        sp: u32,
+        pc: u32,
+        id: usize,
+    },
+    SemaphoreSignal {
+        id: usize,
+    },
+    GpioNextChange,
+    Micros,
+    Kill {
+        id: usize,
+    },
+    TaskInfo {
+        tasks: &'a mut [TaskInfo],
+    },
+    MutexDestroy {
+        id: usize,
+    },
+    SemaphoreDestroy {
+        id: usize,
+    },
+    BarrierDestroy {
+        id: usize,
+    },
+}
+
+// Status returned by Join in r0, the exit code follows in r1 for JOIN_EXITED.
+// JOIN_NO_EXIT_CODE is returned for a task which didn't exit by itself or
+// doesn't exist.
+pub const JOIN_EXITED: u32 = 0;
+pub const JOIN_NO_EXIT_CODE: u32 = 1;
+pub const JOIN_OWN_TASK: u32 = 2;
+
+pub const NO_PIN_CHANGE: u32 = u32::MAX;
+
+// Returned by MutexLock instead of locking, the first one if the task already
+// holds the mutex, the second one if the mutex doesn't exist or was destroyed
+// while the task waited for it
+pub const MUTEX_HELD: u32 = u32::MAX;
+pub const MUTEX_INVALID: u32 = u32::MAX - 1;
+
+// Returned by SemaphoreWait if the semaphore doesn't exist or was destroyed
+// while the task waited for it
+pub const SEMAPHORE_INVALID: u32 = u32::MAX;
+
+// Returned by BarrierWait if the barrier doesn't exist or was destroyed while
+// the task waited at it
+pub const BARRIER_INVALID: u32 = u32::MAX;
+
+// Why the scheduler last had nothing to run
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(u32)]
+pub enum SchedulerStatus {
+    // there always was a task to run so far
+    Running = 0,
+    // every live task waits for a timeout or an event
+    AllWaiting = 1,
+    AllTerminated = 2,
+}
+
+impl From<u32> for SchedulerStatus {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => SchedulerStatus::AllWaiting,
+            2 => SchedulerStatus::AllTerminated,
+            _ => SchedulerStatus::Running,
+        }
+    }
+}
+
+// Memory usage of the whole system, filled in by the kernel
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct MemInfo {
+    pub total_pages: u32,
+    pub used_pages: u32,
+    pub kernel_heap_used: u32,
+    pub kernel_heap_free: u32,
+    pub tasks: u32,
+}
+
+pub const MAX_TASKS: usize = 4;
+pub const MAX_TASK_NAME_LEN: usize = 16;
+
+// State of a task as reported by TaskInfo, terminated tasks aren't listed
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[repr(u32)]
+pub enum TaskStateTag {
+    #[default]
+    Ready = 0,
+    Running = 1,
+    // sleeping or waiting for a timeout
+    Sleeping = 2,
+    // waiting for an event, e.g. a mutex or another task to exit
+    Blocked = 3,
+}
+
+// Entry of the task list filled in by the kernel
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct TaskInfo {
+    pub id: u32,
+    pub state: TaskStateTag,
+    pub priority: u8,
+    name: [u8; MAX_TASK_NAME_LEN],
+    name_len: u8,
+}
+
+impl TaskInfo {
+    pub fn new(id: u32, state: TaskStateTag, priority: u8, name: &[u8]) -> Self {
+        let len = name.len().min(MAX_TASK_NAME_LEN);
+        let mut info = TaskInfo {
+            id,
+            state,
+            priority,
+            name: [0; MAX_TASK_NAME_LEN],
+            name_len: len as u8,
+        };
+        info.name[..len].copy_from_slice(&name[..len]);
+
+        info
+    }
+
+    // Names are only set through SetName, which doesn't check for UTF-8
+    pub fn name(&self) -> &[u8] {
+        &self.name[..self.name_len as usize]
+    }
+}
+
+// Resources a task can check for readiness without blocking
+#[derive(Clone, Copy)]
+pub enum PollTarget {
+    I2cIdle,
+    GpioEdge { pin: GpioPin },
 }
 
 impl Syscall<'_> {
     pub fn call(self) -> Option<SyscallReturnValue> {
         match self {
-            Syscall::Exit => unsafe {
-                asm!("svc 0x0", options(noreturn));
+            Syscall::Exit { code } => unsafe {
+                asm!("svc 0x0", in("r0") code, options(noreturn));
             },
             Syscall::Yield { sp, pc, until } => unsafe {
                 asm!("svc 0x1", in("r0") sp, in("r1") pc, in("r2") until.unwrap_or(0), options(noreturn));
@@ -88,6 +348,290 @@ impl Syscall<'_> {
                 asm!("svc 0x8", in("r0") ptr, in("r1") layout.size(), in("r2") layout.align(), lateout("r0") _);
                 None
             },
+            Syscall::GpioPulse {
+                pin: (pin, bank),
+                micros,
+            } => unsafe {
+                asm!("svc 0x9", in("r0") bank as u32, in("r1") pin, in("r2") micros, lateout("r0") _);
+                None
+            },
+            Syscall::SleepCancelable { sp, pc, until } => unsafe {
+                asm!("svc 0xA", in("r0") sp, in("r1") pc, in("r2") until, options(noreturn));
+            },
+            Syscall::Wake { id } => unsafe {
+                let woken: u32;
+
+                asm!("svc 0xB", in("r0") id, lateout("r0") woken);
+
+                Some(SyscallReturnValue { woken: woken != 0 })
+            },
+            Syscall::GpioCompareSwap {
+                pin: (pin, bank),
+                expected,
+                new,
+            } => unsafe {
+                let swapped: u32;
+
+                asm!("svc 0xC", in("r0") bank as u32, in("r1") pin, in("r2") expected as u32, in("r3") new as u32, lateout("r0") swapped);
+
+                Some(SyscallReturnValue {
+                    gpio_swapped: swapped != 0,
+                })
+            },
+            Syscall::SetQuantum { millis } => unsafe {
+                asm!("svc 0xD", in("r0") millis, lateout("r0") _);
+                None
+            },
+            Syscall::TaskHeapRemaining => unsafe {
+                let remaining: usize;
+
+                asm!("svc 0xE", out("r0") remaining);
+
+                Some(SyscallReturnValue {
+                    heap_remaining: remaining,
+                })
+            },
+            Syscall::Poll { what } => unsafe {
+                let (kind, bank, pin) = match what {
+                    PollTarget::I2cIdle => (0, 0, 0),
+                    PollTarget::GpioEdge { pin: (pin, bank) } => (1, bank as u32, pin),
+                };
+                let ready: u32;
+
+                asm!("svc 0xF", in("r0") kind, in("r1") bank, in("r2") pin, lateout("r0") ready);
+
+                Some(SyscallReturnValue { ready: ready != 0 })
+            },
+            Syscall::DumpPageTable => unsafe {
+                asm!("svc 0x10", lateout("r0") _);
+                None
+            },
+            Syscall::MemInfo { info } => unsafe {
+                asm!("svc 0x11", in("r0") info as *mut MemInfo, lateout("r0") _);
+                None
+            },
+            Syscall::BarrierCreate { parties } => unsafe {
+                let barrier: u32;
+
+                asm!("svc 0x12", in("r0") parties, lateout("r0") barrier);
+
+                Some(SyscallReturnValue { barrier })
+            },
+            Syscall::BarrierWait { sp, pc, id } => unsafe {
+                asm!("svc 0x13", in("r0") sp, in("r1") pc, in("r2") id, options(noreturn));
+            },
+            Syscall::SleepMicros { micros } => unsafe {
+                asm!("svc 0x14", in("r0") micros, lateout("r0") _);
+                None
+            },
+            Syscall::Reschedule { sp, pc } => unsafe {
+                asm!("svc 0x15", in("r0") sp, in("r1") pc, options(noreturn));
+            },
+            Syscall::Sleep { sp, pc, millis } => unsafe {
+                asm!("svc 0x16", in("r0") sp, in("r1") pc, in("r2") millis, options(noreturn));
+            },
+            Syscall::SchedulerStatus => unsafe {
+                let status: u32;
+
+                asm!("svc 0x17", out("r0") status);
+
+                Some(SyscallReturnValue {
+                    scheduler_status: status.into(),
+                })
+            },
+            Syscall::I2cRead { address, buffer } => unsafe {
+                let error: u32;
+
+                asm!("svc 0x18", in("r0") address, in("r1") buffer.as_mut_ptr(), in("r2") buffer.len(), lateout("r0") error);
+
+                Some(SyscallReturnValue {
+                    i2c_read: error.into(),
+                })
+            },
+            Syscall::SetName { name } => unsafe {
+                asm!("svc 0x19", in("r0") name.as_ptr(), in("r1") name.len(), lateout("r0") _);
+                None
+            },
+            Syscall::GpioToggle { pin: (pin, bank) } => unsafe {
+                let value: u32;
+
+                asm!("svc 0x1A", in("r0") bank as u32, in("r1") pin, lateout("r0") value);
+
+                Some(SyscallReturnValue {
+                    gpio_toggled: value != 0,
+                })
+            },
+            Syscall::GpioMode {
+                pin: (pin, bank),
+                mode,
+            } => unsafe {
+                asm!("svc 0x1B", in("r0") bank as u32, in("r1") pin, in("r2") mode as u32, lateout("r0") _);
+                None
+            },
+            Syscall::I2cStart { address, data } => unsafe {
+                let handle: u32;
+
+                asm!("svc 0x1C", in("r0") address, in("r1") data.as_ptr(), in("r2") data.len(), lateout("r0") handle);
+
+                Some(SyscallReturnValue { i2c_handle: handle })
+            },
+            Syscall::WaitI2cDone { sp, pc, handle } => unsafe {
+                asm!("svc 0x1D", in("r0") sp, in("r1") pc, in("r2") handle, options(noreturn));
+            },
+            Syscall::Time64 { micros } => unsafe {
+                asm!("svc 0x1E", in("r0") micros as *mut u64, lateout("r0") _);
+                None
+            },
+            Syscall::Print { data } => unsafe {
+                asm!("svc 0x1F", in("r0") data.as_ptr(), in("r1") data.len(), lateout("r0") _);
+                None
+            },
+            Syscall::Spawn { code } => unsafe {
+                let id: u32;
+
+                asm!("svc 0x20", in("r0") code.as_ptr(), in("r1") code.len(), lateout("r0") id);
+
+                Some(SyscallReturnValue { spawned: id })
+            },
+            Syscall::TaskId => unsafe {
+                let id: u32;
+
+                asm!("svc 0x21", out("r0") id);
+
+                Some(SyscallReturnValue { task_id: id })
+            },
+            Syscall::PwmDuty { channel, duty } => unsafe {
+                asm!("svc 0x22", in("r0") channel as u32, in("r1") duty as u32, lateout("r0") _);
+                None
+            },
+            Syscall::AdcRead { channel } => unsafe {
+                let sample: u32;
+
+                asm!("svc 0x23", in("r0") channel as u32, lateout("r0") sample);
+
+                Some(SyscallReturnValue { adc: sample as u16 })
+            },
+            // the pin number and the edge share r3
+            Syscall::GpioWaitEdge {
+                sp,
+                pc,
+                pin: (pin, bank),
+                edge,
+            } => unsafe {
+                asm!("svc 0x24", in("r0") sp, in("r1") pc, in("r2") bank as u32, in("r3") pin | ((edge as u32) << 8), options(noreturn));
+            },
+            Syscall::SpiTransfer { buffer } => unsafe {
+                asm!("svc 0x25", in("r0") buffer.as_mut_ptr(), in("r1") buffer.len(), lateout("r0") _);
+                None
+            },
+            Syscall::WatchdogKick => unsafe {
+                asm!("svc 0x26", lateout("r0") _);
+                None
+            },
+            Syscall::Join { sp, pc, id } => unsafe {
+                asm!("svc 0x27", in("r0") sp, in("r1") pc, in("r2") id, options(noreturn));
+            },
+            Syscall::I2cScan { buffer } => unsafe {
+                let found: u32;
+
+                asm!("svc 0x28", in("r0") buffer.as_mut_ptr(), in("r1") buffer.len(), lateout("r0") found);
+
+                Some(SyscallReturnValue { i2c_found: found })
+            },
+            Syscall::MutexCreate => unsafe {
+                let mutex: u32;
+
+                asm!("svc 0x29", lateout("r0") mutex);
+
+                Some(SyscallReturnValue { mutex })
+            },
+            Syscall::MutexLock { sp, pc, id } => unsafe {
+                asm!("svc 0x2A", in("r0") sp, in("r1") pc, in("r2") id, options(noreturn));
+            },
+            Syscall::MutexUnlock { id } => unsafe {
+                let unlocked: u32;
+
+                asm!("svc 0x2B", in("r0") id, lateout("r0") unlocked);
+
+                Some(SyscallReturnValue {
+                    unlocked: unlocked != 0,
+                })
+            },
+            Syscall::SemaphoreCreate { count } => unsafe {
+                let semaphore: u32;
+
+                asm!("svc 0x2C", in("r0") count, lateout("r0") semaphore);
+
+                Some(SyscallReturnValue { semaphore })
+            },
+            Syscall::SemaphoreWait { sp, pc, id } => unsafe {
+                asm!("svc 0x2D", in("r0") sp, in("r1") pc, in("r2") id, options(noreturn));
+            },
+            Syscall::SemaphoreSignal { id } => unsafe {
+                let signaled: u32;
+
+                asm!("svc 0x2E", in("r0") id, lateout("r0") signaled);
+
+                Some(SyscallReturnValue {
+                    signaled: signaled != 0,
+                })
+            },
+            Syscall::GpioNextChange => unsafe {
+                let change: u32;
+
+                asm!("svc 0x2F", lateout("r0") change);
+
+                Some(SyscallReturnValue { pin_change: change })
+            },
+            Syscall::Micros => unsafe {
+                let micros: u32;
+
+                asm!("svc 0x30", out("r0") micros);
+                Some(SyscallReturnValue { micros })
+            },
+            Syscall::Kill { id } => unsafe {
+                let killed: u32;
+
+                asm!("svc 0x31", in("r0") id, lateout("r0") killed);
+
+                Some(SyscallReturnValue {
+                    killed: killed != 0,
+                })
+            },
+            Syscall::TaskInfo { tasks } => unsafe {
+                let count: u32;
+
+                asm!("svc 0x32", in("r0") tasks.as_mut_ptr(), in("r1") tasks.len(), lateout("r0") count);
+
+                Some(SyscallReturnValue { tasks: count })
+            },
+            Syscall::MutexDestroy { id } => unsafe {
+                let destroyed: u32;
+
+                asm!("svc 0x33", in("r0") id, lateout("r0") destroyed);
+
+                Some(SyscallReturnValue {
+                    destroyed: destroyed != 0,
+                })
+            },
+            Syscall::SemaphoreDestroy { id } => unsafe {
+                let destroyed: u32;
+
+                asm!("svc 0x34", in("r0") id, lateout("r0") destroyed);
+
+                Some(SyscallReturnValue {
+                    destroyed: destroyed != 0,
+                })
+            },
+            Syscall::BarrierDestroy { id } => unsafe {
+                let destroyed: u32;
+
+                asm!("svc 0x35", in("r0") id, lateout("r0") destroyed);
+
+                Some(SyscallReturnValue {
+                    destroyed: destroyed != 0,
+                })
+            },
         }
     }
 }
@@ -95,8 +639,48 @@ impl Syscall<'_> {
 #[repr(C)]
 pub union SyscallReturnValue {
     pub millis: u32,
+    pub micros: u32,
     pub gpio_read: bool,
     pub i2c_write: I2cError,
+    pub i2c_read: I2cError,
+    // handle of the started transfer, 0 if the bus is busy
+    pub i2c_handle: u32,
     pub alloc: *mut u8,
+    pub woken: bool,
+    pub gpio_swapped: bool,
+    pub gpio_toggled: bool,
+    pub heap_remaining: usize,
+    pub ready: bool,
+    // id of the barrier or u32::MAX, if no barrier could be created
+    pub barrier: u32,
+    pub rescheduled: bool,
+    pub scheduler_status: SchedulerStatus,
+    // id of the new task or u32::MAX, if it couldn't be created
+    pub spawned: u32,
+    // u32::MAX if no task is running
+    pub task_id: u32,
+    pub adc: u16,
+    // JOIN_OWN_TASK, the other statuses are returned together with the code
+    pub joined: u32,
+    // number of addresses written to the buffer of I2cScan
+    pub i2c_found: u32,
+    // id of the mutex or u32::MAX, if no mutex could be created
+    pub mutex: u32,
+    // MUTEX_HELD or MUTEX_INVALID if the mutex wasn't locked
+    pub locked: u32,
+    // false if the task didn't own the mutex
+    pub unlocked: bool,
+    // id of the semaphore or u32::MAX, if no semaphore could be created
+    pub semaphore: u32,
+    // false if the semaphore doesn't exist
+    pub signaled: bool,
+    // bank index << 8 | pin | level << 16, NO_PIN_CHANGE if none is queued
+    pub pin_change: u32,
+    // false if there is no such task or it already terminated
+    pub killed: bool,
+    // number of entries TaskInfo filled in
+    pub tasks: u32,
+    // false if the object doesn't exist or the task didn't create it
+    pub destroyed: bool,
     pub none: (),
 }
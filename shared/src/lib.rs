@@ -1,8 +1,11 @@
 #![no_std]
 
 pub mod alloc;
+pub mod collections;
 pub mod gpio;
 pub mod i2c;
 pub mod interrupts;
 pub mod kernel;
+pub mod pwm;
+pub mod sync;
 pub mod sys;
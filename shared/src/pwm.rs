@@ -0,0 +1,37 @@
+// Output of an eHRPWM module, every module drives two outputs A and B
+#[repr(u32)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum PwmChannel {
+    Ehrpwm0A = 0,
+    Ehrpwm0B = 1,
+    Ehrpwm1A = 2,
+    Ehrpwm1B = 3,
+    Ehrpwm2A = 4,
+    Ehrpwm2B = 5,
+}
+
+impl PwmChannel {
+    pub fn module(&self) -> usize {
+        *self as usize / 2
+    }
+
+    pub fn is_a(&self) -> bool {
+        (*self as u32).is_multiple_of(2)
+    }
+}
+
+impl TryFrom<u32> for PwmChannel {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PwmChannel::Ehrpwm0A),
+            1 => Ok(PwmChannel::Ehrpwm0B),
+            2 => Ok(PwmChannel::Ehrpwm1A),
+            3 => Ok(PwmChannel::Ehrpwm1B),
+            4 => Ok(PwmChannel::Ehrpwm2A),
+            5 => Ok(PwmChannel::Ehrpwm2B),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod ringbuf;
+
+pub use ringbuf::RingBuffer;
@@ -0,0 +1,101 @@
+use core::{
+    cell::UnsafeCell,
+    mem::MaybeUninit,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// Queue between exactly one producer, e.g. an interrupt handler, and one
+// consumer, e.g. a task. Neither side masks interrupts, as each index is only
+// written by one of them. N has to be a power of two.
+pub struct RingBuffer<T, const N: usize> {
+    data: [UnsafeCell<MaybeUninit<T>>; N],
+    // both count every value ever pushed and popped and wrap around, which
+    // keeps the slot right as long as N divides the range of usize
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Send, const N: usize> Sync for RingBuffer<T, N> {}
+
+// push and pop are unsafe, as nothing stops two producers or two consumers
+// from racing for the same slot
+#[allow(clippy::missing_safety_doc)]
+impl<T, const N: usize> RingBuffer<T, N> {
+    const MASK: usize = N - 1;
+
+    pub const fn new() -> Self {
+        const { assert!(N.is_power_of_two(), "the capacity has to be a power of two") };
+
+        Self {
+            data: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+
+        head.wrapping_sub(tail)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= N
+    }
+
+    // Producer side, hands the value back if the buffer is full. The caller
+    // has to be the only one pushing, e.g. a single interrupt handler.
+    pub unsafe fn push(&self, value: T) -> Result<(), T> {
+        let head = self.head.load(Ordering::Relaxed);
+        // the consumer has to be done reading the slot before it's reused
+        let tail = self.tail.load(Ordering::Acquire);
+
+        if head.wrapping_sub(tail) >= N {
+            return Err(value);
+        }
+
+        unsafe { (*self.data[head & Self::MASK].get()).write(value) };
+        // publishes the written slot to the consumer
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+
+        Ok(())
+    }
+
+    // Consumer side, the caller has to be the only one popping
+    pub unsafe fn pop(&self) -> Option<T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let head = self.head.load(Ordering::Acquire);
+
+        if head == tail {
+            return None;
+        }
+
+        let value = unsafe { (*self.data[tail & Self::MASK].get()).assume_init_read() };
+        // releases the slot to the producer
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        // the exclusive borrow rules out any other producer or consumer
+        while unsafe { self.pop() }.is_some() {}
+    }
+}
@@ -25,3 +25,8 @@ pub fn read_bit(address: u32, bit: u32) -> bool {
 }
 
 pub fn noop() {}
+
+// Halts the core until the next interrupt, e.g. the timer tick
+pub fn wfi() {
+    unsafe { core::arch::asm!("wfi", options(nomem, nostack, preserves_flags)) };
+}
@@ -13,5 +13,5 @@ fn _start() {
     println!("This is me, Felix");
     gpio::write(GPIO1_21, true);
 
-    exit();
+    exit(0);
 }